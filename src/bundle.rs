@@ -0,0 +1,494 @@
+//! Writing an output as a bundle: a root document that refers to separate files for its
+//! components and paths with relative `$ref`s
+
+use crate::data::{BundleConfiguration, ComponentSplit, PathSplit};
+use crate::merge::json_pointer::JsonPointer;
+use crate::merge::reference_walker::holds_literal_value;
+use crate::output::OutputFormat;
+use anyhow::{anyhow, Context, Result};
+use indexmap::IndexMap;
+use openapiv3::{OpenAPI, ReferenceOr};
+use serde_json::{json, Value as JsonValue};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// The file for path items without tags when the paths are split per tag
+const UNTAGGED: &str = "untagged";
+
+/// Write the output as a bundle whose root document is `root_path`. The other files are written
+/// to the `components` and `paths` directories next to it; files left there by earlier runs are
+/// not removed. Returns every file that was written.
+pub fn write_bundle(
+    root_path: &Path,
+    format: Option<OutputFormat>,
+    output: &OpenAPI,
+    config: &BundleConfiguration,
+) -> Result<Vec<PathBuf>> {
+    let format = format.unwrap_or_else(|| OutputFormat::for_path(root_path));
+    let root_name = root_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("A bundle needs a root file, not {}", root_path.display()))?;
+    let directory = root_path.parent().unwrap_or(Path::new(""));
+
+    let layout = Layout::new(output, config, root_name, format);
+    let files = layout.files(output)?;
+
+    let mut written = Vec::new();
+    for (file, value) in files {
+        let path = directory.join(&file);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        std::fs::write(&path, format.serialize(&value)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Which file every component and path item of the output is written to
+struct Layout<'a> {
+    config: &'a BundleConfiguration,
+    root_name: &'a str,
+    extension: &'static str,
+    /// The file of each path item, relative to the root document
+    path_files: IndexMap<String, String>,
+    /// The file of each component, by kind and name, when they are split per component
+    component_files: IndexMap<(String, String), String>,
+}
+
+impl<'a> Layout<'a> {
+    fn new(
+        output: &OpenAPI,
+        config: &'a BundleConfiguration,
+        root_name: &'a str,
+        format: OutputFormat,
+    ) -> Self {
+        let extension = match format {
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Json | OutputFormat::JsonCompact => "json",
+        };
+
+        let mut path_files = IndexMap::new();
+        let mut used = HashSet::new();
+        let mut tag_files = HashMap::new();
+        for (path, item) in &output.paths.paths {
+            let file = match config.paths {
+                None => break,
+                Some(PathSplit::PerPath) => unique_stem(&mut used, path),
+                Some(PathSplit::PerTag) => {
+                    let tag = first_tag(item).unwrap_or_else(|| UNTAGGED.to_string());
+                    tag_files
+                        .entry(tag)
+                        .or_insert_with_key(|tag| unique_stem(&mut used, tag))
+                        .clone()
+                }
+            };
+            path_files.insert(path.clone(), format!("paths/{}.{}", file, extension));
+        }
+
+        let mut component_files = IndexMap::new();
+        if config.components == Some(ComponentSplit::PerComponent) {
+            if let Ok(JsonValue::Object(kinds)) = serde_json::to_value(&output.components) {
+                for (kind, entries) in component_kinds(&kinds) {
+                    let mut used = HashSet::new();
+                    for name in entries.keys() {
+                        let file = format!(
+                            "components/{}/{}.{}",
+                            kind,
+                            unique_stem(&mut used, name),
+                            extension
+                        );
+                        component_files.insert((kind.clone(), name.clone()), file);
+                    }
+                }
+            }
+        }
+
+        Self {
+            config,
+            root_name,
+            extension,
+            path_files,
+            component_files,
+        }
+    }
+
+    /// The file that holds the location, relative to the root document, and the location within
+    /// that file
+    fn locate(&self, pointer: &JsonPointer) -> (String, JsonPointer) {
+        match pointer.segments() {
+            [first, kind, name, rest @ ..] if first == "components" && !kind.starts_with("x-") => {
+                match self.config.components {
+                    Some(ComponentSplit::PerKind) => {
+                        let file = format!("components/{}.{}", kind, self.extension);
+                        return (file, pointer_from(Some(name), rest));
+                    }
+                    Some(ComponentSplit::PerComponent) => {
+                        if let Some(file) = self.component_files.get(&(kind.clone(), name.clone()))
+                        {
+                            return (file.clone(), pointer_from(None, rest));
+                        }
+                    }
+                    None => {}
+                }
+            }
+            [first, path, rest @ ..] if first == "paths" => {
+                if let Some(file) = self.path_files.get(path) {
+                    let inner = match self.config.paths {
+                        Some(PathSplit::PerTag) => pointer_from(Some(path), rest),
+                        _ => pointer_from(None, rest),
+                    };
+                    return (file.clone(), inner);
+                }
+            }
+            _ => {}
+        }
+        (self.root_name.to_string(), pointer.clone())
+    }
+
+    /// A reference from a file in a directory `depth` levels below the root document
+    fn reference(&self, depth: usize, pointer: &JsonPointer) -> String {
+        let (file, inner) = self.locate(pointer);
+        let fragment = if inner.segments().is_empty() {
+            String::new()
+        } else {
+            inner.to_string()
+        };
+        format!("{}{}{}", "../".repeat(depth), file, fragment)
+    }
+
+    /// The content of every file of the bundle, starting with the root document
+    fn files(&self, output: &OpenAPI) -> Result<Vec<(String, JsonValue)>> {
+        let mut root = serde_json::to_value(output)?;
+        let mut files = Vec::new();
+
+        if let Some(split) = self.config.components {
+            let depth = match split {
+                ComponentSplit::PerKind => 1,
+                ComponentSplit::PerComponent => 2,
+            };
+            let document = self.rewritten(output, depth)?;
+            if let Some(JsonValue::Object(kinds)) = document.get("components") {
+                for (kind, entries) in component_kinds(kinds) {
+                    if split == ComponentSplit::PerKind {
+                        let file = format!("components/{}.{}", kind, self.extension);
+                        files.push((file, JsonValue::Object(entries.clone())));
+                    } else {
+                        for (name, component) in entries {
+                            let file = self.component_files[&(kind.clone(), name.clone())].clone();
+                            files.push((file, component.clone()));
+                        }
+                    }
+                    for name in entries.keys() {
+                        let pointer = JsonPointer::component(kind, name);
+                        if let Some(entry) = pointer.resolve_mut(&mut root) {
+                            *entry = json!({ "$ref": self.reference(0, &pointer) });
+                        }
+                    }
+                }
+            }
+        }
+
+        if self.config.paths.is_some() {
+            let document = self.rewritten(output, 1)?;
+            let mut path_files: IndexMap<&str, serde_json::Map<String, JsonValue>> =
+                IndexMap::new();
+            for (path, file) in &self.path_files {
+                let pointer = JsonPointer::path(path);
+                if let Some(item) = pointer.resolve(&document) {
+                    path_files
+                        .entry(file)
+                        .or_default()
+                        .insert(path.clone(), item.clone());
+                }
+                if let Some(entry) = pointer.resolve_mut(&mut root) {
+                    *entry = json!({ "$ref": self.reference(0, &pointer) });
+                }
+            }
+            for (file, items) in path_files {
+                let content = match self.config.paths {
+                    Some(PathSplit::PerTag) => JsonValue::Object(items),
+                    _ => items
+                        .into_iter()
+                        .next()
+                        .map(|(_, item)| item)
+                        .unwrap_or_default(),
+                };
+                files.push((file.to_string(), content));
+            }
+        }
+
+        for (file, content) in &mut files {
+            make_self_references_local(content, &self.reference_prefix(file));
+        }
+        files.insert(0, (self.root_name.to_string(), root));
+        Ok(files)
+    }
+
+    /// The output with every local reference replaced by a reference from a file `depth`
+    /// directories below the root document
+    fn rewritten(&self, output: &OpenAPI, depth: usize) -> Result<JsonValue> {
+        let mut document = serde_json::to_value(output)?;
+        self.rewrite_references(&mut document, &JsonPointer::root(), depth);
+        Ok(document)
+    }
+
+    /// Rewrite every `$ref` in the JSON form of the output, so that references the typed model
+    /// does not reach, such as those of encoding headers, are rewritten too. Examples and `x-`
+    /// extensions hold literal values and are left alone.
+    fn rewrite_references(&self, value: &mut JsonValue, location: &JsonPointer, depth: usize) {
+        match value {
+            JsonValue::Object(fields) => {
+                for (key, child) in fields.iter_mut() {
+                    match child {
+                        JsonValue::String(reference) if key == "$ref" => {
+                            if let Some(pointer) = JsonPointer::parse(reference) {
+                                *reference = self.reference(depth, &pointer);
+                            }
+                        }
+                        _ if holds_literal_value(location, key, child) => {}
+                        _ => self.rewrite_references(child, &location.child(key.as_str()), depth),
+                    }
+                }
+            }
+            JsonValue::Array(items) => {
+                for (index, child) in items.iter_mut().enumerate() {
+                    self.rewrite_references(child, &location.child(index.to_string()), depth);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// How a file refers to itself after `rewritten`, e.g. `../components/schemas.yaml#`
+    fn reference_prefix(&self, file: &str) -> String {
+        let depth = file.matches('/').count();
+        format!("{}{}#", "../".repeat(depth), file)
+    }
+}
+
+/// Turn references from a file into itself back into local references
+fn make_self_references_local(value: &mut JsonValue, prefix: &str) {
+    match value {
+        JsonValue::Object(fields) => {
+            if let Some(JsonValue::String(reference)) = fields.get_mut("$ref") {
+                if reference == prefix.trim_end_matches('#') {
+                    *reference = "#".to_string();
+                } else if let Some(fragment) = reference.strip_prefix(prefix) {
+                    *reference = format!("#{}", fragment);
+                }
+            }
+            for child in fields.values_mut() {
+                make_self_references_local(child, prefix);
+            }
+        }
+        JsonValue::Array(items) => {
+            for child in items {
+                make_self_references_local(child, prefix);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn component_kinds(
+    kinds: &serde_json::Map<String, JsonValue>,
+) -> impl Iterator<Item = (&String, &serde_json::Map<String, JsonValue>)> {
+    kinds
+        .iter()
+        .filter(|(kind, _)| !kind.starts_with("x-"))
+        .filter_map(|(kind, entries)| Some((kind, entries.as_object()?)))
+}
+
+fn pointer_from(first: Option<&String>, rest: &[String]) -> JsonPointer {
+    JsonPointer::from_segments(first.into_iter().chain(rest).cloned())
+}
+
+fn first_tag(item: &ReferenceOr<openapiv3::PathItem>) -> Option<String> {
+    match item {
+        ReferenceOr::Item(item) => item
+            .iter()
+            .find_map(|(_, operation)| operation.tags.first().cloned()),
+        ReferenceOr::Reference { .. } => None,
+    }
+}
+
+/// A file name for a path or a component. Only letters, digits, `-`, `_` and `.` are kept so
+/// that the name can be used in a reference without escaping.
+fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .trim_matches('/')
+        .chars()
+        .filter(|c| !matches!(c, '{' | '}'))
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if stem.is_empty() || stem.starts_with('.') {
+        format!("_{}", stem)
+    } else {
+        stem
+    }
+}
+
+/// A file name that differs from the ones already used, even on file systems that ignore case
+fn unique_stem(used: &mut HashSet<String>, name: &str) -> String {
+    let stem = file_stem(name);
+    let mut candidate = stem.clone();
+    let mut suffix = 2;
+    while !used.insert(candidate.to_lowercase()) {
+        candidate = format!("{}_{}", stem, suffix);
+        suffix += 1;
+    }
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external_references::resolve_external_references;
+
+    fn output() -> OpenAPI {
+        serde_json::from_value(json!({
+            "openapi": "3.0.3",
+            "info": { "title": "Uploads", "version": "1" },
+            "paths": {
+                "/uploads": {
+                    "post": {
+                        "tags": ["Uploads"],
+                        "requestBody": {
+                            "content": {
+                                "multipart/form-data": {
+                                    "schema": { "$ref": "#/components/schemas/Upload" },
+                                    "encoding": {
+                                        "file": {
+                                            "headers": {
+                                                "X-Rate-Limit": {
+                                                    "$ref": "#/components/headers/RateLimit"
+                                                }
+                                            }
+                                        }
+                                    },
+                                    "example": { "$ref": "#/components/schemas/Upload" }
+                                }
+                            }
+                        },
+                        "responses": { "201": { "description": "created" } }
+                    }
+                },
+                "/health": {
+                    "get": { "responses": { "200": { "description": "ok" } } }
+                }
+            },
+            "components": {
+                "schemas": {
+                    "Upload": {
+                        "type": "object",
+                        "properties": { "file": { "type": "string", "format": "binary" } }
+                    }
+                },
+                "headers": {
+                    "RateLimit": { "schema": { "type": "integer" } }
+                },
+                "securitySchemes": {
+                    "ApiKey": { "type": "apiKey", "in": "header", "name": "X-Api-Key" },
+                    "Legacy": { "$ref": "#/components/securitySchemes/ApiKey" }
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    /// Write the output as a bundle and load it back, returning every file that was written,
+    /// relative to the root document, and the root document with its references resolved
+    fn round_trip(
+        output: &OpenAPI,
+        config: &BundleConfiguration,
+    ) -> (HashMap<String, JsonValue>, JsonValue) {
+        let directory = tempfile::tempdir().unwrap();
+        let root_path = directory.path().join("openapi.json");
+        let written = write_bundle(&root_path, Some(OutputFormat::Json), output, config).unwrap();
+
+        let files: HashMap<String, JsonValue> = written
+            .iter()
+            .map(|path| {
+                let name = path.strip_prefix(directory.path()).unwrap();
+                let content = std::fs::read_to_string(path).unwrap();
+                (
+                    name.to_string_lossy().replace('\\', "/"),
+                    serde_json::from_str(&content).unwrap(),
+                )
+            })
+            .collect();
+        let mut root = files["openapi.json"].clone();
+        resolve_external_references(&mut root, &root_path.to_string_lossy()).unwrap();
+        (files, root)
+    }
+
+    #[test]
+    fn bundles_split_per_kind_and_path_round_trip() {
+        let output = output();
+        let config = BundleConfiguration {
+            components: Some(ComponentSplit::PerKind),
+            paths: Some(PathSplit::PerPath),
+        };
+
+        let (files, root) = round_trip(&output, &config);
+
+        let upload =
+            &files["paths/uploads.json"]["post"]["requestBody"]["content"]["multipart/form-data"];
+        assert_eq!(
+            upload["encoding"]["file"]["headers"]["X-Rate-Limit"]["$ref"],
+            "../components/headers.json#/RateLimit"
+        );
+        assert_eq!(upload["example"]["$ref"], "#/components/schemas/Upload");
+        assert_eq!(
+            files["components/securitySchemes.json"]["Legacy"]["$ref"],
+            "#/ApiKey"
+        );
+        assert_eq!(root, serde_json::to_value(&output).unwrap());
+    }
+
+    #[test]
+    fn bundles_split_per_component_and_tag_round_trip() {
+        let output = output();
+        let config = BundleConfiguration {
+            components: Some(ComponentSplit::PerComponent),
+            paths: Some(PathSplit::PerTag),
+        };
+
+        let (files, root) = round_trip(&output, &config);
+
+        let mut names: Vec<&str> = files.keys().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "components/headers/RateLimit.json",
+                "components/schemas/Upload.json",
+                "components/securitySchemes/ApiKey.json",
+                "components/securitySchemes/Legacy.json",
+                "openapi.json",
+                "paths/Uploads.json",
+                "paths/untagged.json",
+            ]
+        );
+        assert_eq!(
+            files["paths/Uploads.json"]["/uploads"]["post"]["requestBody"]["content"]
+                ["multipart/form-data"]["encoding"]["file"]["headers"]["X-Rate-Limit"]["$ref"],
+            "../components/headers/RateLimit.json"
+        );
+        assert_eq!(
+            files["components/securitySchemes/Legacy.json"]["$ref"],
+            "../../components/securitySchemes/ApiKey.json"
+        );
+        assert_eq!(root, serde_json::to_value(&output).unwrap());
+    }
+}
@@ -1,11 +1,12 @@
 //! Configuration loading and validation
 
 use anyhow::{Context, Result};
-use serde_json;
+use std::collections::HashSet;
 use std::fs;
 use std::path::Path;
 
 use crate::data::Configuration;
+use crate::parsing::SourceDocument;
 
 const STANDARD_CONFIG_FILE: &str = "openapi-merge.json";
 
@@ -28,29 +29,44 @@ pub fn load_configuration(config_path: &std::path::Path) -> Result<Configuration
         )
     })?;
 
-    validate_configuration(&raw_data)
+    validate_configuration(&config_file.display().to_string(), raw_data)
 }
 
 /// Validate and parse configuration
-fn validate_configuration(raw_data: &str) -> Result<Configuration> {
-    // Parse as JSON or YAML
-    let data: serde_json::Value = if let Ok(json) = serde_json::from_str(raw_data) {
-        json
-    } else if let Ok(yaml) = serde_yaml::from_str::<serde_json::Value>(raw_data) {
-        yaml
-    } else {
-        anyhow::bail!("Configuration file must be valid JSON or YAML");
-    };
-
+fn validate_configuration(file: &str, raw_data: String) -> Result<Configuration> {
     // TODO: Add JSON schema validation once we have the schema
     // For now, just deserialize directly
-    let config: Configuration =
-        serde_json::from_value(data).context("Failed to parse configuration")?;
+    let config: Configuration = SourceDocument::parse(file, raw_data)
+        .and_then(|source| source.deserialize())
+        .context("Failed to parse configuration")?;
 
     // Basic validation
     if config.inputs.is_empty() {
         anyhow::bail!("Configuration must have at least one input");
     }
 
+    if let Some(template) = config
+        .hoist_inline_schemas
+        .as_ref()
+        .and_then(|hoist| hoist.name_template.as_deref())
+    {
+        if !template.contains("{operationId}") && !template.contains("{OperationId}") {
+            anyhow::bail!(
+                "The hoistInlineSchemas nameTemplate '{}' must contain {{operationId}} or {{OperationId}}",
+                template
+            );
+        }
+    }
+
+    let mut output_names = HashSet::new();
+    for output in &config.outputs {
+        if !output_names.insert(output.name.as_str()) {
+            anyhow::bail!(
+                "Configuration has more than one output named '{}'",
+                output.name
+            );
+        }
+    }
+
     Ok(config)
 }
@@ -1,7 +1,12 @@
 //! Core data structures for OpenAPI merging
 
+use crate::diagnostics::Diagnostic;
+use crate::merge::json_pointer::JsonPointer;
+use crate::merge::origins::Origins;
+use crate::output::OutputFormat;
+use openapiv3::{Contact, License, OpenAPI};
 use serde::{Deserialize, Serialize};
-use openapiv3::OpenAPI;
+use std::collections::HashMap;
 
 /// Operation selection criteria for filtering operations
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +38,26 @@ pub struct PathModification {
     pub prepend: Option<String>,
 }
 
+/// Tag modification configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagModification {
+    /// Tags that are renamed to exactly the given name, for example: `{ "Admin": "Orders Admin" }`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub rename: HashMap<String, String>,
+
+    /// Prepend these characters to every tag of this input that is not explicitly renamed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+
+    /// Append these characters to every tag of this input that is not explicitly renamed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suffix: Option<String>,
+
+    /// These tags are added to every operation of this input, after renaming.
+    #[serde(rename = "addTags", default, skip_serializing_if = "Vec::is_empty")]
+    pub add_tags: Vec<String>,
+}
+
 /// Description merge behavior configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DescriptionMergeBehaviour {
@@ -41,9 +66,42 @@ pub struct DescriptionMergeBehaviour {
     pub append: bool,
 
     /// You may optionally include a Markdown Title to demarcate this particular section
-    /// of the merged description files.
+    /// of the merged description files. Headings inside the description are shifted down
+    /// so that they sit underneath this title.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<DescriptionTitle>,
+
+    /// The path to a Markdown file, relative to the configuration file, that replaces the
+    /// info.description of this input.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+/// Configuration for the description of the merged output
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DescriptionConfiguration {
+    /// The path to a Markdown file, relative to the configuration file, that is placed before
+    /// the merged descriptions.
+    #[serde(rename = "preambleFile", skip_serializing_if = "Option::is_none")]
+    pub preamble_file: Option<String>,
+
+    /// The path to a Markdown file, relative to the configuration file, that is placed after
+    /// the merged descriptions.
+    #[serde(rename = "postambleFile", skip_serializing_if = "Option::is_none")]
+    pub postamble_file: Option<String>,
+
+    /// If this is set to true, then a table of contents linking to the title of every appended
+    /// description is placed after the preamble.
+    #[serde(rename = "tableOfContents", skip_serializing_if = "Option::is_none")]
+    pub table_of_contents: Option<bool>,
+}
+
+/// The loaded contents that are composed into the description of the merged output
+#[derive(Debug, Clone, Default)]
+pub struct DescriptionOptions {
+    pub preamble: Option<String>,
+    pub postamble: Option<String>,
+    pub table_of_contents: bool,
 }
 
 /// Description title configuration
@@ -92,54 +150,401 @@ pub struct DisputeSuffix {
     pub always_apply: Option<bool>,
 }
 
+/// How the values of a single `x-` extension are combined when more than one input defines it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExtensionMergeStrategy {
+    /// The value from the first input that defines the extension is used.
+    #[default]
+    FirstWins,
+    /// The value from the last input that defines the extension is used.
+    LastWins,
+    /// Objects are merged key by key, recursively. For any other value the later input wins.
+    DeepMerge,
+    /// Arrays are concatenated in input order. For any other value the first input wins.
+    Concatenate,
+}
+
+impl ExtensionMergeStrategy {
+    /// The name of the strategy as it is written in the configuration
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExtensionMergeStrategy::FirstWins => "firstWins",
+            ExtensionMergeStrategy::LastWins => "lastWins",
+            ExtensionMergeStrategy::DeepMerge => "deepMerge",
+            ExtensionMergeStrategy::Concatenate => "concatenate",
+        }
+    }
+}
+
+/// Extension merge configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExtensionMergeConfiguration {
+    /// The strategy used for every extension that does not have an explicit strategy.
+    /// Defaults to firstWins.
+    #[serde(rename = "defaultStrategy", skip_serializing_if = "Option::is_none")]
+    pub default_strategy: Option<ExtensionMergeStrategy>,
+
+    /// The strategy to use for specific extension keys, for example: `{ "x-tagGroups": "concatenate" }`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub strategies: HashMap<String, ExtensionMergeStrategy>,
+
+    /// If this is set to true, then the merge fails when two inputs define the same extension
+    /// on the same object with different values and the strategy would discard one of them.
+    #[serde(rename = "failOnConflict", skip_serializing_if = "Option::is_none")]
+    pub fail_on_conflict: Option<bool>,
+}
+
+impl ExtensionMergeConfiguration {
+    pub fn strategy_for(&self, key: &str) -> ExtensionMergeStrategy {
+        self.strategies
+            .get(key)
+            .copied()
+            .or(self.default_strategy)
+            .unwrap_or_default()
+    }
+}
+
+/// How the version of the merged info object is chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VersionStrategy {
+    /// Use the version of the first input.
+    #[default]
+    First,
+    /// Use the highest semantic version of all inputs. Versions that are not valid semantic
+    /// versions are ignored.
+    HighestSemver,
+    /// Use a short hash of the content of all inputs, so the version changes whenever an input does.
+    Hash,
+    /// Use the date that the most recently modified input file was last changed, in the format
+    /// YYYY.MM.DD. Falls back to the hash when no input has a modification date.
+    Date,
+}
+
+/// Where the contact and license of the merged info object come from, if not set explicitly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum InfoFieldSource {
+    /// Use the value of the first input, even if it is not set.
+    #[default]
+    First,
+    /// Use the value of the first input that sets it.
+    FirstDefined,
+    /// Leave the value out of the output.
+    Omit,
+}
+
+/// Info configuration for the merged output
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InfoConfiguration {
+    /// The title of the merged output.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    /// The version of the merged output. Takes precedence over the versionStrategy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    /// How the version is chosen if it is not set explicitly. Defaults to first.
+    #[serde(rename = "versionStrategy", skip_serializing_if = "Option::is_none")]
+    pub version_strategy: Option<VersionStrategy>,
+
+    /// The terms of service of the merged output.
+    #[serde(rename = "termsOfService", skip_serializing_if = "Option::is_none")]
+    pub terms_of_service: Option<String>,
+
+    /// The contact of the merged output.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contact: Option<Contact>,
+
+    /// The license of the merged output.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<License>,
+
+    /// Where the contact and license come from if they are not set explicitly. Defaults to first.
+    #[serde(rename = "contactAndLicense", skip_serializing_if = "Option::is_none")]
+    pub contact_and_license: Option<InfoFieldSource>,
+}
+
+/// How the paths of the output are ordered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PathOrdering {
+    /// In the order of the inputs, and of the paths within each input.
+    #[default]
+    Input,
+    /// Sorted by the path template as a string.
+    Lexicographic,
+    /// Sorted segment by segment, with literal segments before templated ones, so that
+    /// `/users/me` comes before `/users/{id}`. Each path comes before the paths below it.
+    Specificity,
+}
+
+/// How the fields of objects in the output are ordered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FieldOrder {
+    /// Fixed fields in the order of the OpenAPI specification, and everything else in the order
+    /// of the inputs.
+    #[default]
+    Input,
+    /// Fixed fields in the order of the OpenAPI specification, responses by status code and
+    /// extensions by name.
+    Recommended,
+}
+
+/// Ordering of the output, to keep it stable when the inputs change in unrelated ways
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OrderingConfiguration {
+    /// How paths are ordered. Defaults to input.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paths: Option<PathOrdering>,
+
+    /// If this is set to true, then the components of each kind are sorted by name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub components: Option<bool>,
+
+    /// If this is set to true, then the tag definitions are sorted by name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<bool>,
+
+    /// How the fields of objects are ordered. Defaults to input.
+    #[serde(rename = "fieldOrder", skip_serializing_if = "Option::is_none")]
+    pub field_order: Option<FieldOrder>,
+}
+
+/// Moves the inline object schemas of operations into `components/schemas`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HoistConfiguration {
+    /// The name of each extracted schema. `{operationId}` is replaced with the operationId of
+    /// the operation, `{OperationId}` with the same starting with a capital letter, and `{role}`
+    /// with where the schema was found, e.g. `Request` or `Response200`. Defaults to
+    /// `{operationId}{role}`.
+    #[serde(rename = "nameTemplate", skip_serializing_if = "Option::is_none")]
+    pub name_template: Option<String>,
+}
+
+/// Filters the operations of a named output. An operation is kept when it matches every kind
+/// of include filter that is set, and none of the exclude filters. Path items that are left
+/// without operations are dropped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OperationFilter {
+    /// Only operations with at least one of these tags are kept.
+    #[serde(rename = "includeTags", skip_serializing_if = "Option::is_none")]
+    pub include_tags: Option<Vec<String>>,
+
+    /// Operations with any of these tags are dropped.
+    #[serde(rename = "excludeTags", skip_serializing_if = "Option::is_none")]
+    pub exclude_tags: Option<Vec<String>>,
+
+    /// Only operations on these paths, or below them, are kept. For example `/admin` keeps
+    /// `/admin` and `/admin/users` but not `/administrators`.
+    #[serde(rename = "includePaths", skip_serializing_if = "Option::is_none")]
+    pub include_paths: Option<Vec<String>>,
+
+    /// Operations on these paths, or below them, are dropped.
+    #[serde(rename = "excludePaths", skip_serializing_if = "Option::is_none")]
+    pub exclude_paths: Option<Vec<String>>,
+
+    /// Only operations that set at least one of these extensions, on the operation or on its
+    /// path item, to a value other than `false` or `null` are kept.
+    #[serde(rename = "includeExtensions", skip_serializing_if = "Option::is_none")]
+    pub include_extensions: Option<Vec<String>>,
+
+    /// Operations that set any of these extensions, e.g. `x-internal: true`, are dropped.
+    #[serde(rename = "excludeExtensions", skip_serializing_if = "Option::is_none")]
+    pub exclude_extensions: Option<Vec<String>>,
+}
+
+/// Replacements for fields of the merged info object in a named output
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InfoOverride {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    #[serde(rename = "termsOfService", skip_serializing_if = "Option::is_none")]
+    pub terms_of_service: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contact: Option<Contact>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<License>,
+}
+
+/// How the components of a bundle are split into files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ComponentSplit {
+    /// One file per kind of component, e.g. `components/schemas.yaml`.
+    PerKind,
+    /// One file per component, e.g. `components/schemas/User.yaml`.
+    PerComponent,
+}
+
+/// How the paths of a bundle are split into files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PathSplit {
+    /// One file per path item, e.g. `paths/users_{id}.yaml`.
+    PerPath,
+    /// One file per tag, e.g. `paths/users.yaml`, holding every path item whose first operation
+    /// has that tag. Path items without tags go to `paths/untagged.yaml`.
+    PerTag,
+}
+
+/// Writes an output as a root document that refers to separate files with relative `$ref`s.
+/// The files are written next to the root document and the bundle loads back as the same
+/// document.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BundleConfiguration {
+    /// How the components are split. Components stay in the root document if this is not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub components: Option<ComponentSplit>,
+
+    /// How the paths are split. Paths stay in the root document if this is not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paths: Option<PathSplit>,
+}
+
+/// Inlines the references to components in an output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Dereference {
+    /// Keep every component, e.g. for the security schemes and for tools that list schemas.
+    KeepComponents,
+    /// Drop the components that are no longer referenced once their references are inlined.
+    DropComponents,
+}
+
+/// A named output that is produced from the same merged inputs as every other output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputConfiguration {
+    /// The name of the output, used in log messages.
+    pub name: String,
+
+    /// The file to write this output to, relative to the configuration file, or `-` for
+    /// standard output.
+    pub output: String,
+
+    /// The format of the output. Defaults to YAML for the .yml and .yaml extensions and JSON
+    /// otherwise.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<OutputFormat>,
+
+    /// Which operations of the merged output are kept in this output.
+    #[serde(rename = "operationFilter", skip_serializing_if = "Option::is_none")]
+    pub operation_filter: Option<OperationFilter>,
+
+    /// If this is set to true, then components that are not referenced, directly or through
+    /// other components, from outside of the components are dropped. Security schemes are kept
+    /// while a security requirement names them.
+    #[serde(rename = "pruneComponents", skip_serializing_if = "Option::is_none")]
+    pub prune_components: Option<bool>,
+
+    /// Replacements for fields of the info object of this output.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub info: Option<InfoOverride>,
+
+    /// Inline every reference to a component in this output. References in recursive schemas
+    /// are kept.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dereference: Option<Dereference>,
+
+    /// Write this output as a bundle of files instead of a single file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bundle: Option<BundleConfiguration>,
+}
+
+/// What happens when the merged output contains references that do not resolve
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DanglingReferenceBehaviour {
+    /// The merge fails.
+    #[default]
+    Error,
+    /// The merge succeeds and the dangling references are reported.
+    Warn,
+}
+
 /// Single merge input
 #[derive(Debug, Clone)]
 pub struct SingleMergeInput {
     pub oas: OpenAPI,
+    pub name: Option<String>,
+    /// The file or URL that the input was loaded from, used when reporting errors.
+    pub source: Option<String>,
     pub path_modification: Option<PathModification>,
     pub operation_selection: Option<OperationSelection>,
+    pub tag_modification: Option<TagModification>,
     pub description: Option<DescriptionMergeBehaviour>,
     pub dispute: Option<Dispute>,
     #[allow(dead_code)] // Deprecated but kept for compatibility
     pub dispute_prefix: Option<String>,
+    /// When the input last changed, if that is known. Inputs loaded from URLs have no date.
+    pub modified: Option<std::time::SystemTime>,
 }
 
 /// Merge input - array of single merge inputs
 pub type MergeInput = Vec<SingleMergeInput>;
 
-/// Error types for merge operations
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum ErrorType {
-    NoInputs,
-    DuplicatePaths,
-    ComponentDefinitionConflict,
-    OperationIdConflict,
+/// Options that apply to the merge as a whole rather than to a single input
+#[derive(Debug, Clone, Default)]
+pub struct MergeOptions {
+    /// The OpenAPI version of the output. Uses the version from the first input if not set.
+    pub openapi_version: Option<String>,
+    /// How `x-` extensions from the inputs are combined.
+    pub extensions: Option<ExtensionMergeConfiguration>,
+    /// Generate an `x-tagGroups` extension with one group per input.
+    pub tag_groups: bool,
+    /// Drop every tag definition that is not used by an operation in the output.
+    pub drop_unused_tags: bool,
+    /// Overrides and strategies for the info object of the output.
+    pub info: Option<InfoConfiguration>,
+    /// Content that is composed around the merged description.
+    pub description: DescriptionOptions,
+    /// What happens when the output contains references that do not resolve.
+    pub dangling_references: DanglingReferenceBehaviour,
+    /// Diagnostic codes that are left out of the result.
+    pub suppressed_warnings: Vec<String>,
+    /// Whether path items, operations and components are stamped with `x-merge-source`.
+    pub annotate_sources: bool,
+    /// How paths, components, tags and fields are ordered in the output.
+    pub ordering: OrderingConfiguration,
+    /// Move the inline object schemas of operations into the components before merging them.
+    pub hoist_inline_schemas: Option<HoistConfiguration>,
 }
 
-/// Error result from merge operation
-#[derive(Debug, Clone)]
-pub struct ErrorMergeResult {
-    pub error_type: ErrorType,
-    pub message: String,
+/// A local `$ref` in the merged output that points at nothing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DanglingReference {
+    /// The value of the `$ref`.
+    pub reference: String,
+    /// The location of the `$ref` in the merged output.
+    pub location: JsonPointer,
+    /// The input that the object containing the `$ref` came from, if known.
+    pub input_index: Option<usize>,
+    /// The location of the `$ref` in that input, if known.
+    pub input_pointer: Option<JsonPointer>,
 }
 
 /// Successful merge result
 #[derive(Debug, Clone)]
 pub struct SuccessfulMergeResult {
     pub output: OpenAPI,
-}
-
-/// Merge result - either success or error
-#[derive(Debug, Clone)]
-pub enum MergeResult {
-    Success(SuccessfulMergeResult),
-    Error(ErrorMergeResult),
-}
-
-impl MergeResult {
-    pub fn is_error(&self) -> bool {
-        matches!(self, MergeResult::Error(_))
-    }
+    /// References in the output that do not resolve, when configured to warn about them.
+    pub dangling_references: Vec<DanglingReference>,
+    /// Everything the merge found worth reporting that did not stop it.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The input that each path item, component and root element of the output came from.
+    pub origins: Origins,
 }
 
 /// Configuration input from file
@@ -166,6 +571,10 @@ pub struct ConfigurationInputFromUrl {
 /// Base configuration input properties
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConfigurationInputBase {
+    /// A name for this input. Used as the name of its tag group; defaults to info.title.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
     /// For this input, you can perform these modifications to its paths elements.
     #[serde(rename = "pathModification", skip_serializing_if = "Option::is_none")]
     pub path_modification: Option<PathModification>,
@@ -174,6 +583,11 @@ pub struct ConfigurationInputBase {
     #[serde(rename = "operationSelection", skip_serializing_if = "Option::is_none")]
     pub operation_selection: Option<OperationSelection>,
 
+    /// For this input, you can rename the tags of its operations and tag definitions. Runs after
+    /// the operation selection, so the selection uses the original tag names.
+    #[serde(rename = "tagModification", skip_serializing_if = "Option::is_none")]
+    pub tag_modification: Option<TagModification>,
+
     /// This configuration setting lets you configure how the info.description from this OpenAPI
     /// file will be merged into the final resulting OpenAPI file
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -186,6 +600,15 @@ pub struct ConfigurationInputBase {
     /// The prefix that will be used in the event of a conflict of two definition names (deprecated).
     #[serde(rename = "disputePrefix", skip_serializing_if = "Option::is_none")]
     pub dispute_prefix: Option<String>,
+
+    /// Replace the `$ref`s in this input that point at other files or URLs with what they point
+    /// at, e.g. to load a bundle written by this tool. The referenced files are read, and URLs
+    /// fetched, every time the input is loaded.
+    #[serde(
+        rename = "resolveExternalReferences",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub resolve_external_references: Option<bool>,
 }
 
 /// Configuration input - either from file or URL
@@ -197,6 +620,13 @@ pub enum ConfigurationInput {
 }
 
 impl ConfigurationInput {
+    pub fn name(&self) -> Option<&String> {
+        match self {
+            ConfigurationInput::FromFile(input) => input.base.name.as_ref(),
+            ConfigurationInput::FromUrl(input) => input.base.name.as_ref(),
+        }
+    }
+
     pub fn path_modification(&self) -> Option<&PathModification> {
         match self {
             ConfigurationInput::FromFile(input) => input.base.path_modification.as_ref(),
@@ -211,6 +641,13 @@ impl ConfigurationInput {
         }
     }
 
+    pub fn tag_modification(&self) -> Option<&TagModification> {
+        match self {
+            ConfigurationInput::FromFile(input) => input.base.tag_modification.as_ref(),
+            ConfigurationInput::FromUrl(input) => input.base.tag_modification.as_ref(),
+        }
+    }
+
     pub fn description(&self) -> Option<&DescriptionMergeBehaviour> {
         match self {
             ConfigurationInput::FromFile(input) => input.base.description.as_ref(),
@@ -231,6 +668,14 @@ impl ConfigurationInput {
             ConfigurationInput::FromUrl(input) => input.base.dispute_prefix.as_ref(),
         }
     }
+
+    pub fn resolve_external_references(&self) -> bool {
+        let resolve = match self {
+            ConfigurationInput::FromFile(input) => input.base.resolve_external_references,
+            ConfigurationInput::FromUrl(input) => input.base.resolve_external_references,
+        };
+        resolve.unwrap_or(false)
+    }
 }
 
 /// Configuration for the OpenAPI Merge CLI Tool
@@ -241,11 +686,115 @@ pub struct Configuration {
 
     /// The output file to put the results in. If you use the .yml or .yaml extension then
     /// the schema will be output in YAML format, otherwise, it will be output in JSON format.
-    pub output: String,
+    /// Use `-` to write the results to standard output. May be left out when `outputs` is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+
+    /// Inline every reference to a component in the output. References in recursive schemas
+    /// are kept.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dereference: Option<Dereference>,
+
+    /// Write the output as a bundle of files instead of a single file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bundle: Option<BundleConfiguration>,
+
+    /// Further outputs with their own selection of the merged result. The inputs are loaded and
+    /// merged once for all of them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<OutputConfiguration>,
 
     /// Optional OpenAPI version to use for the output. If not specified, will use the version
     /// from the first input file.
     #[serde(rename = "openapiVersion", skip_serializing_if = "Option::is_none")]
     pub openapi_version: Option<String>,
+
+    /// How the `x-` extensions of each input are merged into the output. Applies to the root,
+    /// `info`, `tags`, `paths` and `components` objects.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<ExtensionMergeConfiguration>,
+
+    /// If this is set to true, then an `x-tagGroups` extension is generated with one group per
+    /// input, containing the tags of the operations that input contributed. Operations without
+    /// tags are given a tag named after their group.
+    #[serde(rename = "tagGroups", skip_serializing_if = "Option::is_none")]
+    pub tag_groups: Option<bool>,
+
+    /// If this is set to true, then tag definitions that are not used by any operation in the
+    /// output are dropped. Otherwise they are kept unless the operation selection of their input
+    /// filters them out.
+    #[serde(rename = "dropUnusedTags", skip_serializing_if = "Option::is_none")]
+    pub drop_unused_tags: Option<bool>,
+
+    /// Overrides for the info object of the output. Anything not set here is taken from the
+    /// first input.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub info: Option<InfoConfiguration>,
+
+    /// A preamble, postamble and table of contents for the merged description.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<DescriptionConfiguration>,
+
+    /// What happens when a reference in the output points at something that does not exist:
+    /// "error" (the default) fails the merge and "warn" reports the references and continues.
+    #[serde(rename = "danglingReferences", skip_serializing_if = "Option::is_none")]
+    pub dangling_references: Option<DanglingReferenceBehaviour>,
+
+    /// If this is set to true, then every input and the merged output are validated against
+    /// the OpenAPI JSON Schema for their version, and any findings are reported.
+    #[serde(rename = "validateSchema", skip_serializing_if = "Option::is_none")]
+    pub validate_schema: Option<bool>,
+
+    /// Diagnostic codes that should not be reported, e.g. `["unused-tag"]`.
+    #[serde(rename = "suppressWarnings", skip_serializing_if = "Option::is_none")]
+    pub suppress_warnings: Option<Vec<String>>,
+
+    /// If this is set to true, then every path item, operation and component in the output is
+    /// given an `x-merge-source` extension that names the input it came from and its location
+    /// in that input before any renames or path modifications.
+    #[serde(rename = "annotateSources", skip_serializing_if = "Option::is_none")]
+    pub annotate_sources: Option<bool>,
+
+    /// A file to write a source map to, e.g. `openapi-merge.map.json`. The source map gives the
+    /// input file, original JSON pointer, line and column of every traced location in the output.
+    #[serde(rename = "sourceMap", skip_serializing_if = "Option::is_none")]
+    pub source_map: Option<String>,
+
+    /// Sorting of paths, components, tags and fields, so that the output only changes when
+    /// its content does.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ordering: Option<OrderingConfiguration>,
+
+    /// If this is set, then the inline object schemas of request bodies and responses are moved
+    /// into `components/schemas` of their input before merging, so that they are deduplicated
+    /// and disputed like any other schema.
+    #[serde(rename = "hoistInlineSchemas", skip_serializing_if = "Option::is_none")]
+    pub hoist_inline_schemas: Option<HoistConfiguration>,
 }
 
+impl Configuration {
+    /// The options for the merge algorithm described by this configuration. Files referenced by
+    /// the configuration are not loaded; see `file_loading::load_description_options`.
+    pub fn merge_options(&self) -> MergeOptions {
+        MergeOptions {
+            openapi_version: self.openapi_version.clone(),
+            extensions: self.extensions.clone(),
+            tag_groups: self.tag_groups.unwrap_or(false),
+            drop_unused_tags: self.drop_unused_tags.unwrap_or(false),
+            info: self.info.clone(),
+            description: DescriptionOptions {
+                table_of_contents: self
+                    .description
+                    .as_ref()
+                    .and_then(|description| description.table_of_contents)
+                    .unwrap_or(false),
+                ..Default::default()
+            },
+            dangling_references: self.dangling_references.unwrap_or_default(),
+            suppressed_warnings: self.suppress_warnings.clone().unwrap_or_default(),
+            annotate_sources: self.annotate_sources.unwrap_or(false),
+            ordering: self.ordering.clone().unwrap_or_default(),
+            hoist_inline_schemas: self.hoist_inline_schemas.clone(),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Non-fatal findings that are reported alongside a successful merge
+
+use crate::error::ErrorLocation;
+use std::fmt;
+
+/// How much a diagnostic matters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Worth knowing, but the output is what the configuration asked for.
+    Info,
+    /// Something from the inputs was dropped or changed in a way that may not be intended.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "Info"),
+            Severity::Warning => write!(f, "Warning"),
+        }
+    }
+}
+
+/// A finding from the merge that did not stop it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// A short identifier for the kind of finding that does not change between releases.
+    /// These are the codes that can be listed in `suppressWarnings`.
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub location: ErrorLocation,
+}
+
+impl Diagnostic {
+    pub fn warning(
+        code: &'static str,
+        message: impl Into<String>,
+        location: ErrorLocation,
+    ) -> Self {
+        Self {
+            code,
+            severity: Severity::Warning,
+            message: message.into(),
+            location,
+        }
+    }
+
+    pub fn info(code: &'static str, message: impl Into<String>, location: ErrorLocation) -> Self {
+        Self {
+            code,
+            severity: Severity::Info,
+            message: message.into(),
+            location,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} [{}]: {}{}",
+            self.severity, self.code, self.message, self.location
+        )
+    }
+}
+
+/// The security schemes of an input were dropped because an earlier input defined some
+pub const SECURITY_SCHEMES_DISCARDED: &str = "security-schemes-discarded";
+/// A component was renamed with a numeric suffix to avoid a conflict
+pub const COMPONENT_RENAMED: &str = "component-renamed";
+/// An operationId was renamed with a numeric suffix to avoid a conflict
+pub const OPERATION_ID_RENAMED: &str = "operation-id-renamed";
+/// A tag definition in the output is not used by any operation
+pub const UNUSED_TAG: &str = "unused-tag";
+/// The servers of an input were dropped because an earlier input defined some
+pub const SERVERS_DISCARDED: &str = "servers-discarded";
+/// The externalDocs of an input were dropped because an earlier input defined them
+pub const EXTERNAL_DOCS_IGNORED: &str = "external-docs-ignored";
+/// An input declares a different OpenAPI version than the first input
+pub const OPENAPI_VERSION_MISMATCH: &str = "openapi-version-mismatch";
+/// Inputs define different values for an extension and the merge strategy discarded one
+pub const EXTENSION_CONFLICT: &str = "extension-conflict";
+/// A reference in the output does not resolve, with `danglingReferences` set to `warn`
+pub const DANGLING_REFERENCE: &str = "dangling-reference";
@@ -0,0 +1,137 @@
+//! Semantic differences between two JSON or YAML documents
+
+use crate::merge::json_pointer::JsonPointer;
+use serde_json::Value as JsonValue;
+use std::fmt;
+
+/// A single difference between an expected and an actual document
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocumentChange {
+    /// A value is in the actual document but not in the expected one.
+    Added { pointer: String, value: JsonValue },
+    /// A value is in the expected document but not in the actual one.
+    Removed { pointer: String, value: JsonValue },
+    /// A value differs between the documents.
+    Changed {
+        pointer: String,
+        expected: JsonValue,
+        actual: JsonValue,
+    },
+}
+
+impl fmt::Display for DocumentChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocumentChange::Added { pointer, value } => {
+                write!(f, "+ {}: {}", pointer, summarize(value))
+            }
+            DocumentChange::Removed { pointer, value } => {
+                write!(f, "- {}: {}", pointer, summarize(value))
+            }
+            DocumentChange::Changed {
+                pointer,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "~ {}: {} -> {}",
+                pointer,
+                summarize(expected),
+                summarize(actual)
+            ),
+        }
+    }
+}
+
+/// Compare two documents, ignoring formatting and the order of object keys. Additions and
+/// removals are reported once at the highest location that differs, not for every value beneath it.
+pub fn diff_documents(expected: &JsonValue, actual: &JsonValue) -> Vec<DocumentChange> {
+    let mut changes = Vec::new();
+    diff_values(expected, actual, &JsonPointer::root(), &mut changes);
+    changes
+}
+
+fn diff_values(
+    expected: &JsonValue,
+    actual: &JsonValue,
+    pointer: &JsonPointer,
+    changes: &mut Vec<DocumentChange>,
+) {
+    match (expected, actual) {
+        (JsonValue::Object(expected_fields), JsonValue::Object(actual_fields)) => {
+            for (key, expected_value) in expected_fields {
+                let child = pointer.child(key.as_str());
+                match actual_fields.get(key) {
+                    Some(actual_value) => {
+                        diff_values(expected_value, actual_value, &child, changes)
+                    }
+                    None => changes.push(DocumentChange::Removed {
+                        pointer: child.to_string(),
+                        value: expected_value.clone(),
+                    }),
+                }
+            }
+            for (key, actual_value) in actual_fields {
+                if !expected_fields.contains_key(key) {
+                    changes.push(DocumentChange::Added {
+                        pointer: pointer.child(key.as_str()).to_string(),
+                        value: actual_value.clone(),
+                    });
+                }
+            }
+        }
+        (JsonValue::Array(expected_items), JsonValue::Array(actual_items)) => {
+            for (index, expected_item) in expected_items.iter().enumerate() {
+                let child = pointer.child(index.to_string());
+                match actual_items.get(index) {
+                    Some(actual_item) => diff_values(expected_item, actual_item, &child, changes),
+                    None => changes.push(DocumentChange::Removed {
+                        pointer: child.to_string(),
+                        value: expected_item.clone(),
+                    }),
+                }
+            }
+            for (index, actual_item) in actual_items.iter().enumerate().skip(expected_items.len()) {
+                changes.push(DocumentChange::Added {
+                    pointer: pointer.child(index.to_string()).to_string(),
+                    value: actual_item.clone(),
+                });
+            }
+        }
+        _ => {
+            if !values_equal(expected, actual) {
+                changes.push(DocumentChange::Changed {
+                    pointer: pointer.to_string(),
+                    expected: expected.clone(),
+                    actual: actual.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// Numbers are equal when they have the same value, so that `1` and `1.0` are not a difference
+fn values_equal(expected: &JsonValue, actual: &JsonValue) -> bool {
+    match (expected, actual) {
+        (JsonValue::Number(expected), JsonValue::Number(actual)) => {
+            expected == actual || expected.as_f64() == actual.as_f64()
+        }
+        _ => expected == actual,
+    }
+}
+
+/// A single line summary of a value, shortening long strings and containers
+fn summarize(value: &JsonValue) -> String {
+    const MAX_LENGTH: usize = 60;
+    let summary = match value {
+        JsonValue::Object(fields) => format!("{{{} fields}}", fields.len()),
+        JsonValue::Array(items) => format!("[{} items]", items.len()),
+        _ => value.to_string(),
+    };
+    if summary.chars().count() > MAX_LENGTH {
+        let shortened: String = summary.chars().take(MAX_LENGTH - 3).collect();
+        format!("{}...", shortened)
+    } else {
+        summary
+    }
+}
@@ -0,0 +1,237 @@
+//! Errors that stop a merge
+
+use crate::data::SingleMergeInput;
+use crate::merge::json_pointer::JsonPointer;
+use std::fmt;
+use thiserror::Error;
+
+/// Where an error happened: the input it came from and the locations it refers to
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorLocation {
+    /// The index of the input in the configuration.
+    pub input_index: Option<usize>,
+    /// The name of the input, if the configuration gives it one.
+    pub input_name: Option<String>,
+    /// The file or URL that the input was loaded from.
+    pub source_file: Option<String>,
+    /// The location in the input, before any renames or path modifications.
+    pub input_pointer: Option<JsonPointer>,
+    /// The location in the merged output.
+    pub output_pointer: Option<JsonPointer>,
+}
+
+impl ErrorLocation {
+    /// A location in the given input
+    pub fn for_input(input_index: usize, input: &SingleMergeInput) -> Self {
+        Self {
+            input_index: Some(input_index),
+            input_name: input.name.clone(),
+            source_file: input.source.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Set the location in the input
+    pub fn with_input_pointer(mut self, pointer: JsonPointer) -> Self {
+        self.input_pointer = Some(pointer);
+        self
+    }
+
+    /// Set the location in the merged output
+    pub fn with_output_pointer(mut self, pointer: JsonPointer) -> Self {
+        self.output_pointer = Some(pointer);
+        self
+    }
+}
+
+impl fmt::Display for ErrorLocation {
+    /// Written as a parenthesised suffix, e.g. ` (input 1 'users', users.yaml, output #/paths/~1a)`,
+    /// or nothing at all when the location is unknown.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        match (self.input_index, &self.input_name) {
+            (Some(index), Some(name)) => parts.push(format!("input {} '{}'", index, name)),
+            (Some(index), None) => parts.push(format!("input {}", index)),
+            (None, Some(name)) => parts.push(format!("input '{}'", name)),
+            (None, None) => {}
+        }
+        if let Some(source_file) = &self.source_file {
+            parts.push(source_file.clone());
+        }
+        if let Some(pointer) = &self.input_pointer {
+            parts.push(format!("input {}", pointer));
+        }
+        if let Some(pointer) = &self.output_pointer {
+            parts.push(format!("output {}", pointer));
+        }
+
+        if parts.is_empty() {
+            Ok(())
+        } else {
+            write!(f, " ({})", parts.join(", "))
+        }
+    }
+}
+
+/// One of the places an error was found, for errors that are reported in several places at once
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorOccurrence {
+    /// The offending value, e.g. an extension key or a `$ref`.
+    pub value: String,
+    /// Where the value was found.
+    pub location: ErrorLocation,
+}
+
+impl fmt::Display for ErrorOccurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}'{}", self.value, self.location)
+    }
+}
+
+/// The broad kind of an error. The CLI uses a different exit code for each category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    /// The configuration cannot be merged, e.g. it has no inputs.
+    Configuration,
+    /// Two inputs define the same thing in ways that cannot be reconciled.
+    Conflict,
+    /// References in the inputs or the output cannot be resolved.
+    Reference,
+    /// Something went wrong inside the merge itself.
+    Internal,
+}
+
+/// An error that stops a merge. Locations are boxed to keep the error small, since it is
+/// returned from most of the merge functions.
+#[derive(Debug, Clone, Error)]
+pub enum MergeError {
+    #[error("You must provide at least one OAS file as an input.")]
+    NoInputs,
+
+    #[error("Failed to copy the input: {message}{location}")]
+    Serialization {
+        message: String,
+        location: Box<ErrorLocation>,
+    },
+
+    #[error("The path '{original_path}' maps to '{new_path}' and this has already been added by another input file{location}")]
+    DuplicatePath {
+        original_path: String,
+        new_path: String,
+        location: Box<ErrorLocation>,
+    },
+
+    #[error("The \"{key}\" definition in {kind} had a duplicate in a previous input and could not be deduplicated{location}")]
+    ComponentConflict {
+        kind: String,
+        key: String,
+        location: Box<ErrorLocation>,
+    },
+
+    #[error("Could not resolve a conflict for the operationId '{operation_id}'{location}")]
+    OperationIdConflict {
+        operation_id: String,
+        location: Box<ErrorLocation>,
+    },
+
+    #[error("Inputs define conflicting values for these extensions: {}", join(.conflicts))]
+    ExtensionConflict { conflicts: Vec<ErrorOccurrence> },
+
+    #[error("The reference '{reference}' matches more than one renamed element: {}{location}", .candidates.join(", "))]
+    AmbiguousReference {
+        reference: String,
+        candidates: Vec<String>,
+        location: Box<ErrorLocation>,
+    },
+
+    #[error("The merged output contains references that do not resolve: {}", join(.references))]
+    DanglingReference { references: Vec<ErrorOccurrence> },
+}
+
+impl MergeError {
+    /// A short identifier for the kind of error that does not change between releases
+    pub fn code(&self) -> &'static str {
+        match self {
+            MergeError::NoInputs => "no-inputs",
+            MergeError::Serialization { .. } => "serialization",
+            MergeError::DuplicatePath { .. } => "duplicate-path",
+            MergeError::ComponentConflict { .. } => "component-conflict",
+            MergeError::OperationIdConflict { .. } => "operation-id-conflict",
+            MergeError::ExtensionConflict { .. } => "extension-conflict",
+            MergeError::AmbiguousReference { .. } => "ambiguous-reference",
+            MergeError::DanglingReference { .. } => "dangling-reference",
+        }
+    }
+
+    /// The broad kind of this error
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            MergeError::NoInputs => ErrorCategory::Configuration,
+            MergeError::Serialization { .. } => ErrorCategory::Internal,
+            MergeError::DuplicatePath { .. }
+            | MergeError::ComponentConflict { .. }
+            | MergeError::OperationIdConflict { .. }
+            | MergeError::ExtensionConflict { .. } => ErrorCategory::Conflict,
+            MergeError::AmbiguousReference { .. } | MergeError::DanglingReference { .. } => {
+                ErrorCategory::Reference
+            }
+        }
+    }
+
+    /// Where the error happened. Errors that are reported in several places return the first.
+    pub fn location(&self) -> Option<&ErrorLocation> {
+        match self {
+            MergeError::NoInputs => None,
+            MergeError::Serialization { location, .. }
+            | MergeError::DuplicatePath { location, .. }
+            | MergeError::ComponentConflict { location, .. }
+            | MergeError::OperationIdConflict { location, .. }
+            | MergeError::AmbiguousReference { location, .. } => Some(location.as_ref()),
+            MergeError::ExtensionConflict {
+                conflicts: occurrences,
+            }
+            | MergeError::DanglingReference {
+                references: occurrences,
+            } => occurrences.first().map(|occurrence| &occurrence.location),
+        }
+    }
+
+    /// Attribute the error to an input, keeping any pointers that are already set
+    pub fn for_input(mut self, input_index: usize, input: &SingleMergeInput) -> Self {
+        let input_location = ErrorLocation::for_input(input_index, input);
+        if let Some(location) = self.location_mut() {
+            location.input_index = input_location.input_index;
+            location.input_name = input_location.input_name;
+            location.source_file = input_location.source_file;
+        }
+        self
+    }
+
+    /// Set the locations in the input and the output, if the error has a single location
+    pub fn at(mut self, input_pointer: JsonPointer, output_pointer: JsonPointer) -> Self {
+        if let Some(location) = self.location_mut() {
+            location.input_pointer = Some(input_pointer);
+            location.output_pointer = Some(output_pointer);
+        }
+        self
+    }
+
+    fn location_mut(&mut self) -> Option<&mut ErrorLocation> {
+        match self {
+            MergeError::Serialization { location, .. }
+            | MergeError::DuplicatePath { location, .. }
+            | MergeError::ComponentConflict { location, .. }
+            | MergeError::OperationIdConflict { location, .. }
+            | MergeError::AmbiguousReference { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+}
+
+fn join(occurrences: &[ErrorOccurrence]) -> String {
+    occurrences
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,249 @@
+//! Resolving `$ref`s to other files, so that documents split over several files (such as the
+//! bundles written by `bundle::write_bundle`) load as a single document
+
+use crate::file_loading::{fetch_url, read_file};
+use crate::merge::json_pointer::JsonPointer;
+use crate::merge::reference_walker::holds_literal_value;
+use crate::parsing::SourceDocument;
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use url::Url;
+
+/// A location in a document: the file or URL of the document and a pointer into it
+type Target = (String, JsonPointer);
+
+/// Replace every `$ref` to another file in `document`, which was loaded from `source`, with the
+/// value that it points at.
+///
+/// The first location that a value is pulled into becomes its home, and any other reference to
+/// the same value becomes a local reference to that location. Values referenced from
+/// `components` in the root document are given their home there first, so a root document that
+/// lists its components as references to other files gets its local component references back.
+/// References that lead back into a value that is still being pulled in are made local too, so
+/// recursive schemas split over several files resolve.
+pub fn resolve_external_references(document: &mut JsonValue, source: &str) -> Result<()> {
+    let mut sites = Vec::new();
+    collect_references(document, &JsonPointer::root(), &mut sites);
+    sites.retain(|(_, reference)| !reference.starts_with('#'));
+    if sites.is_empty() {
+        return Ok(());
+    }
+    sites.sort_by_key(|(location, _)| {
+        location.segments().first().map(String::as_str) != Some("components")
+    });
+
+    let root = normalize_location(source);
+    let mut resolver = Resolver {
+        root: root.clone(),
+        documents: HashMap::new(),
+        homes: HashMap::new(),
+    };
+    let mut targets = Vec::new();
+    for (location, reference) in sites {
+        let target = resolver.target(&root, &reference)?;
+        resolver
+            .homes
+            .entry(target.clone())
+            .or_insert_with(|| location.clone());
+        targets.push((location, target));
+    }
+
+    for (location, target) in targets {
+        let home = &resolver.homes[&target];
+        let value = if *home == location {
+            resolver.pull_in(&target, &location)?
+        } else {
+            reference_to(home)
+        };
+        if let Some(site) = location.resolve_mut(document) {
+            *site = value;
+        }
+    }
+    Ok(())
+}
+
+struct Resolver {
+    root: String,
+    /// The documents loaded so far, by their normalized file path or URL
+    documents: HashMap<String, JsonValue>,
+    /// Where each referenced value lives in the resolved document
+    homes: HashMap<Target, JsonPointer>,
+}
+
+impl Resolver {
+    /// The document and pointer that a reference made from `base` points at
+    fn target(&self, base: &str, reference: &str) -> Result<Target> {
+        let (file, fragment) = match reference.split_once('#') {
+            Some((file, fragment)) => (file, fragment),
+            None => (reference, ""),
+        };
+        let document = if file.is_empty() {
+            base.to_string()
+        } else {
+            join_location(base, file)?
+        };
+        let pointer = JsonPointer::parse(&format!("#{}", fragment))
+            .ok_or_else(|| anyhow!("Invalid reference '{}' in {}", reference, base))?;
+        Ok((document, pointer))
+    }
+
+    /// Copy the value at `target` to `location` in the resolved document, resolving the
+    /// references inside of it
+    fn pull_in(&mut self, target: &Target, location: &JsonPointer) -> Result<JsonValue> {
+        let (document, pointer) = target;
+        let mut value = pointer
+            .resolve(self.document(document)?)
+            .cloned()
+            .ok_or_else(|| anyhow!("{} does not exist in {}", pointer, document))?;
+
+        let mut references = Vec::new();
+        collect_references(&value, location, &mut references);
+        for (site_location, reference) in references {
+            let target = self.target(document, &reference)?;
+            let relative = site_location
+                .rebase(location, &JsonPointer::root())
+                .unwrap_or_default();
+            let replacement = if target.0 == self.root {
+                reference_to(&target.1)
+            } else if let Some(home) = self.homes.get(&target) {
+                reference_to(home)
+            } else {
+                self.homes.insert(target.clone(), site_location.clone());
+                self.pull_in(&target, &site_location)?
+            };
+            if let Some(site) = relative.resolve_mut(&mut value) {
+                *site = replacement;
+            }
+        }
+        Ok(value)
+    }
+
+    fn document(&mut self, location: &str) -> Result<&JsonValue> {
+        if !self.documents.contains_key(location) {
+            let contents = if is_url(location) {
+                fetch_url(location)?
+            } else {
+                read_file(Path::new(location))?
+            };
+            let source = SourceDocument::parse(location, contents)?;
+            self.documents.insert(location.to_string(), source.document);
+        }
+        Ok(&self.documents[location])
+    }
+}
+
+/// Every local file that resolving the references of the file at `root` reads, found by
+/// following the references from file to file. Files that cannot be read or parsed are
+/// included but not followed. URLs, and the files they refer to, are left out.
+pub fn referenced_files(root: &Path) -> Vec<PathBuf> {
+    let root = normalize_location(&root.display().to_string());
+    let mut files = Vec::new();
+    let mut pending = vec![root.clone()];
+    while let Some(file) = pending.pop() {
+        let document = match read_file(Path::new(&file))
+            .and_then(|contents| Ok(SourceDocument::parse(&file, contents)?))
+        {
+            Ok(source) => source.document,
+            Err(_) => continue,
+        };
+        let mut references = Vec::new();
+        collect_references(&document, &JsonPointer::root(), &mut references);
+        for (_, reference) in references {
+            let referenced = match reference.split_once('#') {
+                Some((referenced, _)) => referenced,
+                None => reference.as_str(),
+            };
+            if referenced.is_empty() || is_url(referenced) {
+                continue;
+            }
+            if let Ok(referenced) = join_location(&file, referenced) {
+                if referenced != root && !files.contains(&referenced) {
+                    files.push(referenced.clone());
+                    pending.push(referenced);
+                }
+            }
+        }
+    }
+    files.into_iter().map(PathBuf::from).collect()
+}
+
+fn reference_to(pointer: &JsonPointer) -> JsonValue {
+    serde_json::json!({ "$ref": pointer.to_string() })
+}
+
+/// Find every `$ref`, with the location of the object that holds it. Examples and `x-`
+/// extensions hold literal values, so the `$ref`s inside of them are not collected.
+fn collect_references(
+    value: &JsonValue,
+    location: &JsonPointer,
+    references: &mut Vec<(JsonPointer, String)>,
+) {
+    match value {
+        JsonValue::Object(fields) => {
+            if let Some(JsonValue::String(reference)) = fields.get("$ref") {
+                references.push((location.clone(), reference.clone()));
+                return;
+            }
+            for (key, child) in fields {
+                if !holds_literal_value(location, key, child) {
+                    collect_references(child, &location.child(key), references);
+                }
+            }
+        }
+        JsonValue::Array(items) => {
+            for (index, child) in items.iter().enumerate() {
+                collect_references(child, &location.child(index.to_string()), references);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_url(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+/// Resolve a relative file reference against the document it was made in
+fn join_location(base: &str, relative: &str) -> Result<String> {
+    if is_url(relative) {
+        return Ok(relative.to_string());
+    }
+    if is_url(base) {
+        let base = Url::parse(base).with_context(|| format!("Invalid URL: {}", base))?;
+        let joined = base
+            .join(relative)
+            .with_context(|| format!("Invalid reference '{}' in {}", relative, base))?;
+        return Ok(joined.to_string());
+    }
+    let directory = Path::new(base).parent().unwrap_or(Path::new(""));
+    Ok(normalize_location(
+        &directory.join(relative).display().to_string(),
+    ))
+}
+
+/// Remove `.` and `..` from a file path, so that the same file is always named the same way
+fn normalize_location(location: &str) -> String {
+    if is_url(location) {
+        return location.to_string();
+    }
+    let mut normalized = PathBuf::new();
+    for component in Path::new(location).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) {
+                    normalized.pop();
+                } else {
+                    normalized.push("..");
+                }
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized.display().to_string()
+}
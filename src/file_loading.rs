@@ -2,13 +2,13 @@
 
 use anyhow::{Context, Result};
 use openapiv3::OpenAPI;
-use serde_json;
-use serde_yaml;
 use std::fs;
 use std::path::Path;
 use url::Url;
 
-use crate::data::ConfigurationInput;
+use crate::data::{ConfigurationInput, DescriptionConfiguration, DescriptionOptions};
+use crate::external_references::resolve_external_references;
+use crate::parsing::SourceDocument;
 
 /// Load an OpenAPI file from a configuration input
 pub fn load_oas_for_input(
@@ -17,77 +17,143 @@ pub fn load_oas_for_input(
     input_index: usize,
     logger: &mut dyn FnMut(&str),
 ) -> Result<OpenAPI> {
+    let source = load_source_for_input(base_path, input, input_index, logger)?;
+    Ok(source.to_openapi()?)
+}
+
+/// Load and parse a configuration input, keeping its text so that later errors can point at
+/// a line and column. References to other files are resolved if the input asks for that. Use
+/// `SourceDocument::to_openapi` to turn it into an OpenAPI file.
+pub fn load_source_for_input(
+    base_path: &Path,
+    input: &ConfigurationInput,
+    input_index: usize,
+    logger: &mut dyn FnMut(&str),
+) -> Result<SourceDocument> {
+    let contents = read_input(base_path, input, input_index, logger)?;
+    let mut source = SourceDocument::parse(&input_source(base_path, input), contents)?;
+    if input.resolve_external_references() {
+        resolve_source_references(&mut source)?;
+    }
+    Ok(source)
+}
+
+/// Resolve the references of a parsed source to other files, so that the document is complete
+pub fn resolve_source_references(source: &mut SourceDocument) -> Result<()> {
+    resolve_external_references(&mut source.document, &source.file)
+        .with_context(|| format!("Failed to resolve the references in {}", source.file))
+}
+
+/// Describe where a configuration input is loaded from: its file path or its URL
+pub fn input_source(base_path: &Path, input: &ConfigurationInput) -> String {
+    match input {
+        ConfigurationInput::FromFile(file_input) => {
+            base_path.join(&file_input.input_file).display().to_string()
+        }
+        ConfigurationInput::FromUrl(url_input) => url_input.input_url.clone(),
+    }
+}
+
+/// When a file input was last modified. URL inputs have no modification date.
+pub fn input_modified(
+    base_path: &Path,
+    input: &ConfigurationInput,
+) -> Option<std::time::SystemTime> {
+    match input {
+        ConfigurationInput::FromFile(file_input) => {
+            fs::metadata(base_path.join(&file_input.input_file))
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        }
+        ConfigurationInput::FromUrl(_) => None,
+    }
+}
+
+fn read_input(
+    base_path: &Path,
+    input: &ConfigurationInput,
+    input_index: usize,
+    logger: &mut dyn FnMut(&str),
+) -> Result<String> {
     match input {
         ConfigurationInput::FromFile(file_input) => {
             let full_path = base_path.join(&file_input.input_file);
-            logger(&format!("## Loading input {}: {}", input_index, full_path.display()));
-            load_from_file(&full_path)
+            logger(&format!(
+                "## Loading input {}: {}",
+                input_index,
+                full_path.display()
+            ));
+            read_file(&full_path)
         }
         ConfigurationInput::FromUrl(url_input) => {
-            logger(&format!("## Loading input {} from URL: {}", input_index, url_input.input_url));
-            load_from_url(&url_input.input_url)
+            logger(&format!(
+                "## Loading input {} from URL: {}",
+                input_index, url_input.input_url
+            ));
+            fetch_url(&url_input.input_url)
         }
     }
 }
 
+/// Load a Markdown file that is referenced by the configuration
+pub fn load_markdown_file(base_path: &Path, file: &str) -> Result<String> {
+    let full_path = base_path.join(file);
+    fs::read_to_string(&full_path)
+        .with_context(|| format!("Failed to read Markdown file: {}", full_path.display()))
+}
+
+/// Load the preamble and postamble files for the merged description
+pub fn load_description_options(
+    base_path: &Path,
+    config: Option<&DescriptionConfiguration>,
+) -> Result<DescriptionOptions> {
+    let config = match config {
+        Some(config) => config,
+        None => return Ok(DescriptionOptions::default()),
+    };
+
+    Ok(DescriptionOptions {
+        preamble: config
+            .preamble_file
+            .as_deref()
+            .map(|file| load_markdown_file(base_path, file))
+            .transpose()?,
+        postamble: config
+            .postamble_file
+            .as_deref()
+            .map(|file| load_markdown_file(base_path, file))
+            .transpose()?,
+        table_of_contents: config.table_of_contents.unwrap_or(false),
+    })
+}
+
 /// Load OpenAPI file from local filesystem
 pub fn load_from_file(file_path: &Path) -> Result<OpenAPI> {
-    let contents = fs::read_to_string(file_path)
-        .with_context(|| format!("Failed to read file: {}", file_path.display()))?;
-    
-    parse_yaml_or_json(&contents)
+    let source = SourceDocument::parse(&file_path.display().to_string(), read_file(file_path)?)?;
+    Ok(source.to_openapi()?)
 }
 
 /// Load OpenAPI file from URL
 pub fn load_from_url(url_str: &str) -> Result<OpenAPI> {
-    let url = Url::parse(url_str)
-        .with_context(|| format!("Invalid URL: {}", url_str))?;
-    
-    let client = reqwest::blocking::Client::new();
-    let response = client.get(url).send()
-        .with_context(|| format!("Failed to fetch URL: {}", url_str))?;
-    
-    let contents = response.text()
-        .with_context(|| format!("Failed to read response from URL: {}", url_str))?;
-    
-    parse_yaml_or_json(&contents)
+    let source = SourceDocument::parse(url_str, fetch_url(url_str)?)?;
+    Ok(source.to_openapi()?)
 }
 
-/// Parse YAML or JSON content into OpenAPI
-fn parse_yaml_or_json(contents: &str) -> Result<OpenAPI> {
-    // Try JSON first
-    if let Ok(openapi) = serde_json::from_str::<OpenAPI>(contents) {
-        return Ok(openapi);
-    }
+pub(crate) fn read_file(file_path: &Path) -> Result<String> {
+    fs::read_to_string(file_path)
+        .with_context(|| format!("Failed to read file: {}", file_path.display()))
+}
 
-    // Try YAML
-    if let Ok(openapi) = serde_yaml::from_str::<OpenAPI>(contents) {
-        return Ok(openapi);
-    }
+pub(crate) fn fetch_url(url_str: &str) -> Result<String> {
+    let url = Url::parse(url_str).with_context(|| format!("Invalid URL: {}", url_str))?;
 
-    // If both fail, try parsing as generic value first
-    let json_value: Result<serde_json::Value, _> = serde_json::from_str(contents);
-    let yaml_value: Result<serde_yaml::Value, _> = serde_yaml::from_str(contents);
+    let client = reqwest::blocking::Client::new();
+    let response = client
+        .get(url)
+        .send()
+        .with_context(|| format!("Failed to fetch URL: {}", url_str))?;
 
-    match (json_value, yaml_value) {
-        (Ok(val), _) => {
-            serde_json::from_value(val)
-                .context("Failed to parse as OpenAPI from JSON")
-        }
-        (_, Ok(val)) => {
-            // Convert YAML value to JSON value for deserialization
-            let json_val = serde_json::to_value(&val)
-                .context("Failed to convert YAML to JSON")?;
-            serde_json::from_value(json_val)
-                .context("Failed to parse as OpenAPI from YAML")
-        }
-        (Err(json_err), Err(yaml_err)) => {
-            anyhow::bail!(
-                "Failed to parse the input as either JSON or YAML.\n\nJSON Error: {}\n\nYAML Error: {}",
-                json_err,
-                yaml_err
-            )
-        }
-    }
+    response
+        .text()
+        .with_context(|| format!("Failed to read response from URL: {}", url_str))
 }
-
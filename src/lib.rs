@@ -1,12 +1,23 @@
 //! OpenAPI Merge Library
-//! 
+//!
 //! A library for merging multiple OpenAPI 3.0 specification files into a single file.
 
+pub mod bundle;
 pub mod config;
 pub mod data;
+pub mod diagnostics;
+pub mod document_diff;
+pub mod error;
+pub mod external_references;
 pub mod file_loading;
 pub mod merge;
+pub mod output;
+pub mod parsing;
+pub mod serve;
+pub mod source_map;
+pub mod validation;
+pub mod watch;
 
-pub use data::{MergeInput, SingleMergeInput, Configuration, ConfigurationInput};
+pub use data::{Configuration, ConfigurationInput, MergeInput, MergeOptions, SingleMergeInput};
+pub use error::MergeError;
 pub use merge::merge;
-
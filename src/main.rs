@@ -1,13 +1,32 @@
 //! OpenAPI Merge CLI
-//! 
+//!
 //! Command-line tool for merging multiple OpenAPI specification files.
 
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use openapi_merge::bundle::write_bundle;
 use openapi_merge::config::load_configuration;
-use openapi_merge::file_loading::load_oas_for_input;
+use openapi_merge::data::{
+    BundleConfiguration, Configuration, ConfigurationInput, Dereference, SingleMergeInput,
+    SuccessfulMergeResult,
+};
+use openapi_merge::diagnostics::Severity;
+use openapi_merge::document_diff::{diff_documents, DocumentChange};
+use openapi_merge::error::{ErrorCategory, MergeError};
+use openapi_merge::file_loading::{
+    input_modified, input_source, load_description_options, load_markdown_file,
+    load_source_for_input, resolve_source_references,
+};
+use openapi_merge::merge::dereference::dereference;
 use openapi_merge::merge::merge;
-use openapi_merge::data::{ConfigurationInput, SingleMergeInput};
+use openapi_merge::merge::output_selection::select_output;
+use openapi_merge::output::{is_stdout, write_output, OutputFormat, STDOUT};
+use openapi_merge::parsing::SourceDocument;
+use openapi_merge::serve::{serve, Preview};
+use openapi_merge::source_map::{build_source_map, locate_positions};
+use openapi_merge::validation::{validate_openapi_document, SchemaFinding};
+use openapi_merge::watch::{local_files, FileWatcher};
+use std::borrow::Cow;
 use std::path::PathBuf;
 use std::time::Instant;
 
@@ -16,14 +35,90 @@ use std::time::Instant;
 #[command(version)]
 #[command(about = "A CLI tool for merging multiple OpenAPI specification files")]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Path to the configuration file
-    #[arg(short, long, default_value = "openapi-merge.json")]
+    #[arg(short, long, global = true, default_value = "openapi-merge.json")]
     config: PathBuf,
+
+    /// Validate every input and the merged output against the OpenAPI JSON Schema
+    #[arg(long, global = true)]
+    validate: bool,
+
+    /// Validate like --validate, and fail if anything does not match the schema
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Fail without writing the output if the merge reports any warnings
+    #[arg(long, global = true)]
+    deny_warnings: bool,
+
+    /// Write a source map to this file, instead of the sourceMap file in the configuration
+    #[arg(long)]
+    source_map: Option<PathBuf>,
+
+    /// Write the output to this file instead of the output file in the configuration, or to
+    /// standard output if it is `-`
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// The format of the output: json, yaml or json-compact. Without this, the format is chosen
+    /// from the extension of the output file, and standard output is written as JSON
+    #[arg(long)]
+    format: Option<OutputFormat>,
+
+    /// Check that the output file is up to date with the inputs, without writing anything
+    #[arg(long)]
+    check: bool,
+
+    /// Merge again whenever the configuration or a local input changes, until interrupted
+    #[arg(long, conflicts_with = "check")]
+    watch: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Merge the inputs and serve the output on localhost, merging again when they change.
+    /// The output file is not written.
+    ///
+    /// The output is served as it would be written to `output`, at /openapi.json and
+    /// /openapi.yaml, and rendered at /. The docs page loads Redoc from its CDN, so viewing it
+    /// needs internet access.
+    Serve {
+        /// The port to listen on
+        #[arg(short, long, default_value_t = 8080)]
+        port: u16,
+    },
 }
 
+/// The inputs were merged successfully
+struct Merged {
+    config: Configuration,
+    inputs: Vec<SingleMergeInput>,
+    input_contents: Vec<String>,
+    result: SuccessfulMergeResult,
+}
+
+/// The configuration could not be loaded, or it has no inputs
 const ERROR_LOADING_CONFIG: i32 = 1;
+/// An input or a Markdown file could not be loaded
 const ERROR_LOADING_INPUTS: i32 = 2;
+/// The merge failed internally, or the output could not be written
 const ERROR_MERGING: i32 = 3;
+/// Schema validation failed in strict mode
+const ERROR_VALIDATING: i32 = 4;
+/// Inputs define paths, components, operationIds or extensions that conflict
+const ERROR_CONFLICT: i32 = 5;
+/// References could not be rewritten or do not resolve in the output
+const ERROR_REFERENCE: i32 = 6;
+/// The merge reported warnings and --deny-warnings was passed
+const ERROR_WARNINGS: i32 = 7;
+/// The output file does not match the merged inputs and --check was passed
+const ERROR_OUT_OF_DATE: i32 = 8;
+
+/// The most differences that --check prints before summarising the rest
+const MAX_REPORTED_CHANGES: usize = 50;
 
 struct LogWithMillisDiff {
     prev_time: Instant,
@@ -39,7 +134,8 @@ impl LogWithMillisDiff {
     fn log(&mut self, message: &str) {
         let now = Instant::now();
         let elapsed = now.duration_since(self.prev_time).as_millis();
-        println!("{} (+{}ms)", message, elapsed);
+        // Logs go to standard error so that they do not mix with output written to standard output
+        eprintln!("{} (+{}ms)", message, elapsed);
         self.prev_time = now;
     }
 }
@@ -54,105 +150,484 @@ fn main() {
 fn run() -> Result<()> {
     let cli = Cli::parse();
     let mut logger = LogWithMillisDiff::new();
-    
-    logger.log(&format!("## Running openapi-merge v{}", env!("CARGO_PKG_VERSION")));
 
+    logger.log(&format!(
+        "## Running openapi-merge v{}",
+        env!("CARGO_PKG_VERSION")
+    ));
+
+    if let Some(Command::Serve { port }) = cli.command {
+        return serve_preview(&cli, port, &mut logger);
+    }
+
+    if !cli.watch {
+        if let Err(exit_code) = merge_and_write(&cli, &mut logger) {
+            std::process::exit(exit_code);
+        }
+        return Ok(());
+    }
+
+    watch_and_run(&cli, &mut logger, |logger| {
+        // Keep the last good output and carry on watching when the merge fails
+        let _ = merge_and_write(&cli, logger);
+    })
+}
+
+/// Merge the inputs and serve the primary output on localhost until interrupted
+fn serve_preview(cli: &Cli, port: u16, logger: &mut LogWithMillisDiff) -> Result<()> {
+    let preview = Preview::default();
+    let address = format!("127.0.0.1:{}", port);
+
+    serve(&address, preview.clone())?;
+    logger.log(&format!(
+        "## Serving the merged output on http://{}/",
+        address
+    ));
+
+    watch_and_run(cli, logger, |logger| {
+        let updated = load_and_merge(cli, logger)
+            .and_then(|merged| {
+                Ok(primary_output(&merged.config, &merged.result.output)?.into_owned())
+            })
+            .map_err(|exit_code| {
+                format!(
+                    "The merge failed with exit code {}, see the terminal for details",
+                    exit_code
+                )
+            })
+            .and_then(|primary| {
+                preview
+                    .update(&primary)
+                    .map_err(|e| format!("Error serializing output: {}", e))
+            });
+        match updated {
+            Ok(()) => logger.log(&format!(
+                "## Inputs merged, serving the results on http://{}/",
+                address
+            )),
+            Err(e) => preview.fail(e),
+        }
+    })
+}
+
+/// Run `run` now and again every time the configuration or a local file it reads changes
+fn watch_and_run(
+    cli: &Cli,
+    logger: &mut LogWithMillisDiff,
+    mut run: impl FnMut(&mut LogWithMillisDiff),
+) -> ! {
+    loop {
+        // Look at the files before merging, so that changes made during the merge are not missed
+        let mut watcher = FileWatcher::new(watched_files(cli));
+
+        run(logger);
+
+        logger.log(&format!(
+            "## Watching {} files for changes",
+            watcher.files().count()
+        ));
+        let changed = watcher.wait_for_changes();
+        for path in &changed {
+            logger.log(&format!("## Changed: {}", path.display()));
+        }
+    }
+}
+
+/// Load the configuration and the inputs, merge them and write every output. Errors are printed
+/// as they happen, and the exit code for the error is returned.
+fn merge_and_write(cli: &Cli, logger: &mut LogWithMillisDiff) -> std::result::Result<(), i32> {
+    let Merged {
+        config,
+        inputs,
+        input_contents,
+        result,
+    } = load_and_merge(cli, logger)?;
+    let base_path = cli.config.parent().unwrap_or(std::path::Path::new("."));
+    let output = &result.output;
+
+    let mut out_of_date = false;
+
+    // The output on the command line replaces the output in the configuration
+    let output_path = match (&cli.output, &config.output) {
+        (Some(path), _) => Some(path.clone()),
+        (None, Some(path)) => Some(config_output_path(base_path, path)),
+        (None, None) => None,
+    };
+    if output_path.is_none() && config.outputs.is_empty() {
+        eprintln!("Error: the configuration has no output or outputs, and --output was not passed");
+        return Err(ERROR_LOADING_CONFIG);
+    }
+    if let Some(output_path) = &output_path {
+        let primary = primary_output(&config, output)?;
+        out_of_date |= !check_or_write(
+            cli,
+            logger,
+            output_path,
+            cli.format,
+            config.bundle.as_ref(),
+            &primary,
+        )?;
+    }
+
+    for output_config in &config.outputs {
+        logger.log(&format!("## Selecting the output '{}'", output_config.name));
+        let selected = match select_output(output, output_config) {
+            Ok(selected) => selected,
+            Err(e) => {
+                eprintln!(
+                    "Error selecting the output '{}' [{}]: {}",
+                    output_config.name,
+                    e.code(),
+                    e
+                );
+                return Err(merge_error_exit_code(&e));
+            }
+        };
+        let path = config_output_path(base_path, &output_config.output);
+        out_of_date |= !check_or_write(
+            cli,
+            logger,
+            &path,
+            output_config.format,
+            output_config.bundle.as_ref(),
+            &selected,
+        )?;
+    }
+
+    if out_of_date {
+        return Err(ERROR_OUT_OF_DATE);
+    }
+
+    let source_map_path = cli
+        .source_map
+        .clone()
+        .or_else(|| config.source_map.as_ref().map(|file| base_path.join(file)));
+    if let (Some(source_map_path), false) = (source_map_path, cli.check) {
+        let positions: Vec<_> = input_contents
+            .iter()
+            .map(|contents| locate_positions(contents))
+            .collect();
+        let source_map = build_source_map(output, &inputs, &result.origins, &positions);
+        let written = serde_json::to_string_pretty(&source_map)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| {
+                std::fs::write(&source_map_path, content).map_err(anyhow::Error::from)
+            });
+        if let Err(e) = written {
+            eprintln!("Error writing source map: {}", e);
+            return Err(ERROR_MERGING);
+        }
+        logger.log(&format!(
+            "## Wrote the source map to '{}'",
+            source_map_path.display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// The output that `output` in the configuration is written from: the merged result, with its
+/// references inlined if the configuration asks for that. Errors are printed, and the exit code
+/// for the error is returned.
+fn primary_output<'a>(
+    config: &Configuration,
+    output: &'a openapiv3::OpenAPI,
+) -> std::result::Result<Cow<'a, openapiv3::OpenAPI>, i32> {
+    match config.dereference {
+        Some(mode) => match dereference(output, mode == Dereference::DropComponents) {
+            Ok(dereferenced) => Ok(Cow::Owned(dereferenced)),
+            Err(e) => {
+                eprintln!("Error inlining references [{}]: {}", e.code(), e);
+                Err(merge_error_exit_code(&e))
+            }
+        },
+        None => Ok(Cow::Borrowed(output)),
+    }
+}
+
+/// Resolve an output path from the configuration, which is relative to the configuration file
+fn config_output_path(base_path: &std::path::Path, path: &str) -> PathBuf {
+    if path == STDOUT {
+        PathBuf::from(STDOUT)
+    } else {
+        base_path.join(path)
+    }
+}
+
+/// Write an output, or with --check compare it with the existing file. Returns whether the
+/// file is up to date, which it always is after writing it. A bundle is compared with the
+/// document that its files load as.
+fn check_or_write(
+    cli: &Cli,
+    logger: &mut LogWithMillisDiff,
+    output_path: &std::path::Path,
+    format: Option<OutputFormat>,
+    bundle: Option<&BundleConfiguration>,
+    output: &openapiv3::OpenAPI,
+) -> std::result::Result<bool, i32> {
+    if bundle.is_some() && is_stdout(output_path) {
+        eprintln!("Error: a bundle needs an output file to write its root document to, not standard output");
+        return Err(ERROR_LOADING_CONFIG);
+    }
+
+    if cli.check {
+        if is_stdout(output_path) {
+            eprintln!("Error: --check needs an output file to compare with, not standard output");
+            return Err(ERROR_LOADING_CONFIG);
+        }
+        logger.log(&format!(
+            "## Inputs merged, comparing the results with '{}'",
+            output_path.display()
+        ));
+        let changes = match check_output(output_path, bundle.is_some(), output) {
+            Ok(changes) => changes,
+            Err(e) => {
+                eprintln!(
+                    "Error: '{}' is not up to date: {:#}",
+                    output_path.display(),
+                    e
+                );
+                return Ok(false);
+            }
+        };
+        if changes.is_empty() {
+            logger.log(&format!("## '{}' is up to date", output_path.display()));
+            return Ok(true);
+        }
+
+        eprintln!(
+            "Error: '{}' is not up to date, {} differences from the merged inputs:",
+            output_path.display(),
+            changes.len()
+        );
+        for change in changes.iter().take(MAX_REPORTED_CHANGES) {
+            eprintln!("  {}", change);
+        }
+        if changes.len() > MAX_REPORTED_CHANGES {
+            eprintln!("  ... and {} more", changes.len() - MAX_REPORTED_CHANGES);
+        }
+        return Ok(false);
+    }
+
+    let destination = if is_stdout(output_path) {
+        "standard output".to_string()
+    } else {
+        format!("'{}'", output_path.display())
+    };
+    logger.log(&format!(
+        "## Inputs merged, writing the results out to {}",
+        destination
+    ));
+
+    // Write output
+    if let Some(bundle) = bundle {
+        match write_bundle(output_path, format, output, bundle) {
+            Ok(files) => logger.log(&format!("## Wrote a bundle of {} files", files.len())),
+            Err(e) => {
+                eprintln!("Error writing output: {:#}", e);
+                return Err(ERROR_MERGING);
+            }
+        }
+    } else if let Err(e) = write_output(output_path, format, output) {
+        eprintln!("Error writing output: {:#}", e);
+        return Err(ERROR_MERGING);
+    }
+
+    logger.log(&format!("## Finished writing to {}", destination));
+    Ok(true)
+}
+
+/// Load the configuration and the inputs and merge them, validating them if asked to. Errors
+/// are printed as they happen, and the exit code for the error is returned.
+fn load_and_merge(cli: &Cli, logger: &mut LogWithMillisDiff) -> std::result::Result<Merged, i32> {
     // Load configuration
     let config = match load_configuration(&cli.config) {
         Ok(config) => config,
         Err(e) => {
-            eprintln!("{}", e);
-            std::process::exit(ERROR_LOADING_CONFIG);
+            eprintln!("{:#}", e);
+            return Err(ERROR_LOADING_CONFIG);
         }
     };
 
-    logger.log(&format!("## Loaded the configuration: {} inputs", config.inputs.len()));
+    logger.log(&format!(
+        "## Loaded the configuration: {} inputs",
+        config.inputs.len()
+    ));
 
     let base_path = cli.config.parent().unwrap_or(std::path::Path::new("."));
 
+    let validate = cli.validate || cli.strict || config.validate_schema.unwrap_or(false);
+
     // Load all input files
-    let inputs = match convert_inputs(base_path, &config.inputs, &mut logger) {
-        Ok(inputs) => inputs,
-        Err(e) => {
-            eprintln!("{}", e);
-            std::process::exit(ERROR_LOADING_INPUTS);
-        }
-    };
+    let (inputs, input_findings, input_contents) =
+        match convert_inputs(base_path, &config.inputs, validate, logger) {
+            Ok(inputs) => inputs,
+            Err(e) => {
+                eprintln!("{:#}", e);
+                return Err(ERROR_LOADING_INPUTS);
+            }
+        };
+
+    if cli.strict && input_findings > 0 {
+        eprintln!(
+            "Error: {} schema validation findings in the inputs",
+            input_findings
+        );
+        return Err(ERROR_VALIDATING);
+    }
+
+    let mut merge_options = config.merge_options();
+    merge_options.description =
+        match load_description_options(base_path, config.description.as_ref()) {
+            Ok(description) => description,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(ERROR_LOADING_INPUTS);
+            }
+        };
 
     logger.log("## Loaded the inputs into memory, merging the results.");
 
     // Merge the inputs
-    let merge_result = merge(&inputs, config.openapi_version.as_deref());
+    let merge_result = merge(&inputs, &merge_options);
 
     match merge_result {
-        Ok(output) => {
-            let output_path = base_path.join(&config.output);
-            logger.log(&format!("## Inputs merged, writing the results out to '{}'", output_path.display()));
-
-            // Write output
-            if let Err(e) = write_output(&output_path, &output) {
-                eprintln!("Error writing output: {}", e);
-                std::process::exit(ERROR_MERGING);
+        Ok(result) => {
+            for diagnostic in &result.diagnostics {
+                eprintln!("{}", diagnostic);
+            }
+
+            let warning_count = result
+                .diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.severity == Severity::Warning)
+                .count();
+            if cli.deny_warnings && warning_count > 0 {
+                eprintln!("Error: the merge reported {} warnings", warning_count);
+                return Err(ERROR_WARNINGS);
             }
 
-            logger.log(&format!("## Finished writing to '{}'", output_path.display()));
+            if validate {
+                let findings = match serde_json::to_value(&result.output) {
+                    Ok(document) => validate_openapi_document(&document),
+                    Err(e) => {
+                        eprintln!("Error serializing output: {}", e);
+                        return Err(ERROR_MERGING);
+                    }
+                };
+                report_schema_findings("Output", &findings);
+                if cli.strict && !findings.is_empty() {
+                    eprintln!(
+                        "Error: {} schema validation findings in the output",
+                        findings.len()
+                    );
+                    return Err(ERROR_VALIDATING);
+                }
+            }
+
+            Ok(Merged {
+                config,
+                inputs,
+                input_contents,
+                result,
+            })
         }
         Err(e) => {
-            eprintln!("Error merging files: {:?}", e);
-            std::process::exit(ERROR_MERGING);
+            eprintln!("Error merging files [{}]: {}", e.code(), e);
+            Err(merge_error_exit_code(&e))
         }
     }
+}
 
-    Ok(())
+/// The configuration file and every local file it reads
+fn watched_files(cli: &Cli) -> Vec<PathBuf> {
+    let mut files = vec![cli.config.clone()];
+    if let Ok(config) = load_configuration(&cli.config) {
+        let base_path = cli.config.parent().unwrap_or(std::path::Path::new("."));
+        files.extend(local_files(base_path, &config));
+    }
+    files
 }
 
+/// Load every input. When validating, the schema findings for each input are printed and
+/// their total is returned alongside the inputs, followed by the raw contents of each input.
 fn convert_inputs(
     base_path: &std::path::Path,
     config_inputs: &[ConfigurationInput],
+    validate: bool,
     logger: &mut LogWithMillisDiff,
-) -> Result<Vec<SingleMergeInput>> {
+) -> Result<(Vec<SingleMergeInput>, usize, Vec<String>)> {
     let mut inputs = Vec::new();
+    let mut finding_count = 0;
+    let mut contents = Vec::new();
 
     for (input_index, config_input) in config_inputs.iter().enumerate() {
-        let oas = load_oas_for_input(
-            base_path,
-            config_input,
-            input_index,
-            &mut |msg| logger.log(msg),
-        )?;
-        
+        let source = load_source_for_input(base_path, config_input, input_index, &mut |msg| {
+            logger.log(msg)
+        })?;
+
+        if validate {
+            let findings = validate_openapi_document(&source.document);
+            report_schema_findings(&format!("Input {}", input_index), &findings);
+            finding_count += findings.len();
+        }
+
+        let mut oas = source.to_openapi().map_err(|e| {
+            anyhow::Error::from(e)
+                .context(format!("Input {} is not a valid OpenAPI file", input_index))
+        })?;
+        contents.push(source.contents);
+
+        if let Some(file) = config_input.description().and_then(|d| d.file.as_ref()) {
+            oas.info.description = Some(load_markdown_file(base_path, file)?);
+        }
+
         let single_input = SingleMergeInput {
             oas,
+            name: config_input.name().cloned(),
+            source: Some(input_source(base_path, config_input)),
             path_modification: config_input.path_modification().cloned(),
             operation_selection: config_input.operation_selection().cloned(),
+            tag_modification: config_input.tag_modification().cloned(),
             description: config_input.description().cloned(),
             dispute: config_input.dispute().cloned(),
             dispute_prefix: config_input.dispute_prefix().cloned(),
+            modified: input_modified(base_path, config_input),
         };
 
         inputs.push(single_input);
     }
 
-    Ok(inputs)
+    Ok((inputs, finding_count, contents))
 }
 
-fn write_output(output_path: &std::path::Path, output: &openapiv3::OpenAPI) -> Result<()> {
-    use std::fs::File;
-    use std::io::Write;
-
-    let extension = output_path.extension()
-        .and_then(|ext| ext.to_str())
-        .unwrap_or("json");
-
-    let content = if extension == "yaml" || extension == "yml" {
-        serde_yaml::to_string(output)?
-    } else {
-        serde_json::to_string_pretty(output)?
-    };
-
-    let mut file = File::create(output_path)?;
-    file.write_all(content.as_bytes())?;
+fn merge_error_exit_code(error: &MergeError) -> i32 {
+    match error.category() {
+        ErrorCategory::Configuration => ERROR_LOADING_CONFIG,
+        ErrorCategory::Conflict => ERROR_CONFLICT,
+        ErrorCategory::Reference => ERROR_REFERENCE,
+        ErrorCategory::Internal => ERROR_MERGING,
+    }
+}
 
-    Ok(())
+fn report_schema_findings(source: &str, findings: &[SchemaFinding]) {
+    for finding in findings {
+        eprintln!("{}: {}: {}", source, finding.pointer, finding.message);
+    }
 }
 
+/// Compare the merged output with the existing output file, ignoring formatting. The files of
+/// a bundle are loaded into a single document first.
+fn check_output(
+    output_path: &std::path::Path,
+    bundle: bool,
+    output: &openapiv3::OpenAPI,
+) -> Result<Vec<DocumentChange>> {
+    let contents = std::fs::read_to_string(output_path)?;
+    let mut existing = SourceDocument::parse(&output_path.display().to_string(), contents)?;
+    if bundle {
+        resolve_source_references(&mut existing)?;
+    }
+    let merged = serde_json::to_value(output)?;
+    Ok(diff_documents(&existing.document, &merged))
+}
@@ -5,10 +5,7 @@ use serde_json::Value as JsonValue;
 
 /// Check if two schema references are deeply equal
 /// This is a simplified version - full implementation would need reference resolution
-pub fn deep_equality_schema(
-    x: &ReferenceOr<Schema>,
-    y: &ReferenceOr<Schema>,
-) -> bool {
+pub fn deep_equality_schema(x: &ReferenceOr<Schema>, y: &ReferenceOr<Schema>) -> bool {
     // For now, use JSON equality as a proxy
     // A full implementation would need to resolve references and compare recursively
     let x_json = serde_json::to_value(x).unwrap_or(JsonValue::Null);
@@ -25,4 +22,3 @@ where
     let y_json = serde_json::to_value(y).unwrap_or(JsonValue::Null);
     x_json == y_json
 }
-
@@ -0,0 +1,110 @@
+//! Detection of references in the merged output that do not resolve
+
+use crate::data::DanglingReference;
+use crate::merge::json_pointer::JsonPointer;
+use crate::merge::origins::{original_location, Origins};
+use crate::merge::reference_walker::holds_literal_value;
+use openapiv3::OpenAPI;
+use serde_json::Value as JsonValue;
+
+/// Find every local `$ref` in the output that does not resolve to a value in the output.
+/// References to other documents are not checked.
+pub fn find_dangling_references(output: &OpenAPI, origins: &Origins) -> Vec<DanglingReference> {
+    let document = match serde_json::to_value(output) {
+        Ok(document) => document,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut references = Vec::new();
+    collect_references(&document, &JsonPointer::root(), &mut references);
+
+    references
+        .into_iter()
+        .filter(|(_, reference)| !resolves(&document, reference))
+        .map(|(location, reference)| {
+            let origin = original_location(origins, &location);
+            DanglingReference {
+                input_index: origin.as_ref().map(|origin| origin.input_index),
+                input_pointer: origin.map(|origin| origin.original_pointer),
+                reference,
+                location,
+            }
+        })
+        .collect()
+}
+
+fn collect_references(
+    value: &JsonValue,
+    location: &JsonPointer,
+    references: &mut Vec<(JsonPointer, String)>,
+) {
+    match value {
+        JsonValue::Object(fields) => {
+            if let Some(JsonValue::String(reference)) = fields.get("$ref") {
+                if reference.starts_with('#') {
+                    references.push((location.clone(), reference.clone()));
+                }
+            }
+            for (key, child) in fields {
+                if !holds_literal_value(location, key, child) {
+                    collect_references(child, &location.child(key.as_str()), references);
+                }
+            }
+        }
+        JsonValue::Array(items) => {
+            for (index, child) in items.iter().enumerate() {
+                collect_references(child, &location.child(index.to_string()), references);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn resolves(document: &JsonValue, reference: &str) -> bool {
+    JsonPointer::parse(reference)
+        .and_then(|pointer| pointer.resolve(document).map(|_| ()))
+        .is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn references_inside_examples_and_extensions_are_not_checked() {
+        let output: OpenAPI = serde_json::from_value(json!({
+            "openapi": "3.0.3",
+            "info": { "title": "Examples", "version": "1" },
+            "paths": {
+                "/pets": {
+                    "get": {
+                        "x-sample": { "$ref": "#/x-missing" },
+                        "responses": {
+                            "200": {
+                                "description": "ok",
+                                "content": {
+                                    "application/json": {
+                                        "schema": { "$ref": "#/components/schemas/Missing" },
+                                        "example": { "$ref": "#/example" },
+                                        "examples": {
+                                            "literal": { "value": { "$ref": "#/value" } }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }))
+        .unwrap();
+
+        let dangling = find_dangling_references(&output, &Origins::new());
+        let references: Vec<&str> = dangling
+            .iter()
+            .map(|dangling| dangling.reference.as_str())
+            .collect();
+        assert_eq!(references, ["#/components/schemas/Missing"]);
+    }
+}
@@ -0,0 +1,218 @@
+//! Inlining references to components, for consumers that cannot follow `$ref`s
+
+use crate::error::{ErrorLocation, MergeError};
+use crate::merge::json_pointer::JsonPointer;
+use crate::merge::output_selection::prune_components;
+use crate::merge::reference_walker::holds_literal_value;
+use openapiv3::OpenAPI;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+
+/// Replace every reference to a component with a copy of the component, with its own references
+/// inlined in turn. A reference to a component that is already being inlined further up, as in
+/// a recursive schema, is kept. Other fields next to a `$ref`, such as `description`, replace
+/// the fields of the copy. Examples and `x-` extensions hold literal values, so the `$ref`s inside
+/// of them are left as they are.
+///
+/// With `drop_components`, the components that are no longer referenced are dropped afterwards,
+/// in the same way as for `pruneComponents`.
+pub fn dereference(oas: &OpenAPI, drop_components: bool) -> Result<OpenAPI, MergeError> {
+    let serialization_error = |e: serde_json::Error| MergeError::Serialization {
+        message: e.to_string(),
+        location: ErrorLocation::default().into(),
+    };
+    let mut document = serde_json::to_value(oas).map_err(serialization_error)?;
+    let original = document.clone();
+    let mut inliner = Inliner {
+        document: &original,
+        inlined: HashMap::new(),
+    };
+
+    if let JsonValue::Object(fields) = &mut document {
+        for (key, value) in fields.iter_mut() {
+            if key != "components" && !holds_literal_value(&JsonPointer::root(), key, value) {
+                let location = JsonPointer::root().child(key.as_str());
+                inliner.inline(value, &location, &mut Vec::new());
+            }
+        }
+    }
+
+    if let Some(JsonValue::Object(kinds)) = document.get_mut("components") {
+        for (kind, entries) in kinds.iter_mut() {
+            if kind.starts_with("x-") {
+                continue;
+            }
+            if let JsonValue::Object(entries) = entries {
+                for (name, component) in entries.iter_mut() {
+                    let location = JsonPointer::component(kind, name);
+                    let mut stack = vec![location.clone()];
+                    inliner.inline(component, &location, &mut stack);
+                }
+            }
+        }
+    }
+
+    let output = serde_json::from_value(document).map_err(serialization_error)?;
+    if drop_components {
+        prune_components(&output)
+    } else {
+        Ok(output)
+    }
+}
+
+struct Inliner<'a> {
+    /// The document before anything was inlined, which references are resolved in
+    document: &'a JsonValue,
+    /// Components that have been inlined without keeping any recursive reference, so that the
+    /// same copy can be used again
+    inlined: HashMap<JsonPointer, JsonValue>,
+}
+
+impl Inliner<'_> {
+    /// Inline the references in `value`, which is at `location` in the document it was copied
+    /// from, where `stack` holds the components that are being inlined around it. Returns whether
+    /// a recursive reference was kept, in which case the result depends on the stack.
+    fn inline(
+        &mut self,
+        value: &mut JsonValue,
+        location: &JsonPointer,
+        stack: &mut Vec<JsonPointer>,
+    ) -> bool {
+        match value {
+            JsonValue::Object(fields) => {
+                let pointer = match fields.get("$ref") {
+                    Some(JsonValue::String(reference)) => component_pointer(reference),
+                    _ => None,
+                };
+                let pointer = match pointer {
+                    Some(pointer) => pointer,
+                    None => {
+                        let mut recursive = false;
+                        for (key, child) in fields.iter_mut() {
+                            if !holds_literal_value(location, key, child) {
+                                let location = location.child(key.as_str());
+                                recursive |= self.inline(child, &location, stack);
+                            }
+                        }
+                        return recursive;
+                    }
+                };
+
+                if stack.contains(&pointer) {
+                    return true;
+                }
+                let (mut target, recursive) = match self.inlined.get(&pointer) {
+                    Some(target) => (target.clone(), false),
+                    None => {
+                        let mut target = match pointer.resolve(self.document) {
+                            Some(target) => target.clone(),
+                            // Dangling references are reported by the merge, and kept as they are
+                            None => return false,
+                        };
+                        stack.push(pointer.clone());
+                        let recursive = self.inline(&mut target, &pointer, stack);
+                        stack.pop();
+                        if !recursive {
+                            self.inlined.insert(pointer, target.clone());
+                        }
+                        (target, recursive)
+                    }
+                };
+
+                if let JsonValue::Object(target_fields) = &mut target {
+                    for (key, sibling) in fields.iter() {
+                        if key != "$ref" {
+                            target_fields.insert(key.clone(), sibling.clone());
+                        }
+                    }
+                }
+                *value = target;
+                recursive
+            }
+            JsonValue::Array(items) => {
+                let mut recursive = false;
+                for (index, child) in items.iter_mut().enumerate() {
+                    let location = location.child(index.to_string());
+                    recursive |= self.inline(child, &location, stack);
+                }
+                recursive
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The pointer of a local reference to a component, or to something inside one
+fn component_pointer(reference: &str) -> Option<JsonPointer> {
+    let pointer = JsonPointer::parse(reference)?;
+    match pointer.segments() {
+        [first, kind, _, ..] if first == "components" && !kind.starts_with("x-") => Some(pointer),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn references_inside_examples_and_extensions_are_not_inlined() {
+        let literal = json!({ "$ref": "#/components/schemas/Pet" });
+        let oas: OpenAPI = serde_json::from_value(json!({
+            "openapi": "3.0.3",
+            "info": { "title": "Pets", "version": "1" },
+            "x-sample": literal,
+            "paths": {
+                "/pets": {
+                    "get": {
+                        "x-sample": literal,
+                        "responses": {
+                            "200": {
+                                "description": "ok",
+                                "content": {
+                                    "application/json": {
+                                        "schema": { "$ref": "#/components/schemas/Pet" },
+                                        "example": literal,
+                                        "examples": {
+                                            "inline": { "value": literal },
+                                            "shared": { "$ref": "#/components/examples/Pet" }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            },
+            "components": {
+                "schemas": {
+                    "Pet": {
+                        "type": "object",
+                        "properties": { "example": { "$ref": "#/components/schemas/Name" } },
+                        "example": literal
+                    },
+                    "Name": { "type": "string" }
+                },
+                "examples": {
+                    "Pet": { "value": literal }
+                }
+            }
+        }))
+        .unwrap();
+
+        let output = serde_json::to_value(dereference(&oas, false).unwrap()).unwrap();
+
+        let content =
+            &output["paths"]["/pets"]["get"]["responses"]["200"]["content"]["application/json"];
+        assert_eq!(content["schema"]["type"], "object");
+        assert_eq!(content["schema"]["properties"]["example"]["type"], "string");
+        assert_eq!(content["schema"]["example"], literal);
+        assert_eq!(content["example"], literal);
+        assert_eq!(content["examples"]["inline"]["value"], literal);
+        assert_eq!(content["examples"]["shared"]["value"], literal);
+        assert_eq!(output["paths"]["/pets"]["get"]["x-sample"], literal);
+        assert_eq!(output["x-sample"], literal);
+        assert_eq!(output["components"]["examples"]["Pet"]["value"], literal);
+    }
+}
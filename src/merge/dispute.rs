@@ -47,4 +47,3 @@ pub fn apply_dispute(dispute: Option<&Dispute>, input: &str, status: DisputeStat
         Dispute::Suffix(suffix) => format!("{}{}", input, suffix.suffix),
     }
 }
-
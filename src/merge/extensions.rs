@@ -1,44 +1,149 @@
 //! Extension merging logic
 
-use crate::data::MergeInput;
+use crate::data::{ExtensionMergeConfiguration, ExtensionMergeStrategy, MergeInput};
+use crate::error::{ErrorLocation, ErrorOccurrence, MergeError};
+use crate::merge::json_pointer::JsonPointer;
+use indexmap::IndexMap;
 use openapiv3::OpenAPI;
 use serde_json::Value as JsonValue;
 
-/// Merge x-extension fields from all inputs
-pub fn merge_extensions(output: &mut OpenAPI, inputs: &MergeInput) {
-    // Extract extensions from output
-    let mut extensions = extract_extensions(output);
-
-    // Extract and merge extensions from all inputs
-    for input in inputs {
-        let input_extensions = extract_extensions(&input.oas);
-        for (key, value) in input_extensions {
-            if !extensions.contains_key(&key) {
-                extensions.insert(key, value);
+/// An extension that was defined with different values by more than one input, where the
+/// configured strategy had to discard one of the values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtensionConflict {
+    /// The location of the object that holds the extension, e.g. `#/info`.
+    pub location: JsonPointer,
+    /// The extension key, e.g. `x-logo`.
+    pub key: String,
+    /// The input whose value conflicted with the value already in the output.
+    pub input_index: usize,
+    /// The strategy that was used to resolve the conflict.
+    pub strategy: ExtensionMergeStrategy,
+}
+
+/// Merges `x-` extensions at every level of the document and keeps track of the conflicts
+/// that were resolved along the way.
+#[derive(Debug, Default)]
+pub struct ExtensionMerger {
+    config: ExtensionMergeConfiguration,
+    conflicts: Vec<ExtensionConflict>,
+}
+
+impl ExtensionMerger {
+    pub fn new(config: Option<&ExtensionMergeConfiguration>) -> Self {
+        Self {
+            config: config.cloned().unwrap_or_default(),
+            conflicts: Vec::new(),
+        }
+    }
+
+    /// Merge the extensions in `source`, which came from input `input_index`, into `target`
+    pub fn merge(
+        &mut self,
+        target: &mut IndexMap<String, JsonValue>,
+        source: &IndexMap<String, JsonValue>,
+        location: &JsonPointer,
+        input_index: usize,
+    ) {
+        for (key, value) in source.iter().filter(|(key, _)| key.starts_with("x-")) {
+            match target.get_mut(key) {
+                Some(existing) => {
+                    let strategy = self.config.strategy_for(key);
+                    if merge_value(existing, value, strategy) {
+                        self.conflicts.push(ExtensionConflict {
+                            location: location.clone(),
+                            key: key.clone(),
+                            input_index,
+                            strategy,
+                        });
+                    }
+                }
+                None => {
+                    target.insert(key.clone(), value.clone());
+                }
             }
         }
     }
 
-    // Apply extensions back to output
-    // Note: openapiv3 crate may not support extensions directly,
-    // so we may need to serialize/deserialize to add them
-    // For now, this is a placeholder
+    /// Fails with an error if conflicts were found and the configuration asks for that
+    pub fn finish(self, inputs: &MergeInput) -> Result<Vec<ExtensionConflict>, MergeError> {
+        if self.config.fail_on_conflict.unwrap_or(false) && !self.conflicts.is_empty() {
+            let conflicts = self
+                .conflicts
+                .iter()
+                .map(|conflict| ErrorOccurrence {
+                    value: conflict.key.clone(),
+                    location: ErrorLocation::for_input(
+                        conflict.input_index,
+                        &inputs[conflict.input_index],
+                    )
+                    .with_output_pointer(conflict.location.clone()),
+                })
+                .collect();
+            return Err(MergeError::ExtensionConflict { conflicts });
+        }
+
+        Ok(self.conflicts)
+    }
+}
+
+/// Merge x-extension fields from the root of all inputs
+pub fn merge_extensions(output: &mut OpenAPI, inputs: &MergeInput, merger: &mut ExtensionMerger) {
+    for (input_index, input) in inputs.iter().enumerate() {
+        merger.merge(
+            &mut output.extensions,
+            &input.oas.extensions,
+            &JsonPointer::root(),
+            input_index,
+        );
+    }
 }
 
-fn extract_extensions(oas: &OpenAPI) -> std::collections::HashMap<String, JsonValue> {
-    let mut result = std::collections::HashMap::new();
-    
-    // Convert OpenAPI to JSON value to extract extensions
-    if let Ok(json_value) = serde_json::to_value(oas) {
-        if let Some(obj) = json_value.as_object() {
-            for (key, value) in obj {
-                if key.starts_with("x-") {
-                    result.insert(key.clone(), value.clone());
-                }
+/// Combines `incoming` into `existing`. Returns true when a differing value was discarded.
+fn merge_value(
+    existing: &mut JsonValue,
+    incoming: &JsonValue,
+    strategy: ExtensionMergeStrategy,
+) -> bool {
+    match strategy {
+        ExtensionMergeStrategy::FirstWins => existing != incoming,
+        ExtensionMergeStrategy::LastWins => {
+            let conflict = existing != incoming;
+            *existing = incoming.clone();
+            conflict
+        }
+        ExtensionMergeStrategy::DeepMerge => deep_merge(existing, incoming),
+        ExtensionMergeStrategy::Concatenate => {
+            if let (JsonValue::Array(existing_items), JsonValue::Array(incoming_items)) =
+                (&mut *existing, incoming)
+            {
+                existing_items.extend(incoming_items.iter().cloned());
+                false
+            } else {
+                existing != incoming
             }
         }
     }
-
-    result
 }
 
+fn deep_merge(existing: &mut JsonValue, incoming: &JsonValue) -> bool {
+    match (existing, incoming) {
+        (JsonValue::Object(existing_fields), JsonValue::Object(incoming_fields)) => {
+            let mut conflict = false;
+            for (key, value) in incoming_fields {
+                match existing_fields.get_mut(key) {
+                    Some(existing_value) => conflict |= deep_merge(existing_value, value),
+                    None => {
+                        existing_fields.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            conflict
+        }
+        (existing, incoming) => {
+            let conflict = existing != incoming;
+            *existing = incoming.clone();
+            conflict
+        }
+    }
+}
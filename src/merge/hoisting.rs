@@ -0,0 +1,210 @@
+//! Moving the inline schemas of operations into the components
+
+use crate::data::HoistConfiguration;
+use crate::merge::component_equivalence::components_equal;
+use crate::merge::json_pointer::JsonPointer;
+use crate::merge::operation_selection::operation_slots_mut;
+use indexmap::IndexMap;
+use openapiv3::{OpenAPI, Operation, ReferenceOr, Response, Schema, SchemaKind, Type};
+use std::collections::HashMap;
+
+/// The name template used when the configuration does not set one
+pub const DEFAULT_NAME_TEMPLATE: &str = "{operationId}{role}";
+
+/// The location in the input of every schema that was moved into the components, by the name
+/// it was given
+pub type HoistedSchemas = HashMap<String, JsonPointer>;
+
+/// Replace the inline object schemas of request bodies and responses, and the inline object
+/// items of array schemas there, with references to new schemas in `components/schemas`. A
+/// schema that is identical to one that is already in the components is replaced with a
+/// reference to that one instead. Operations without an operationId are named after their
+/// method and path, e.g. `getUsersId`.
+pub fn hoist_inline_schemas(oas: &mut OpenAPI, config: &HoistConfiguration) -> HoistedSchemas {
+    let mut hoister = Hoister {
+        template: config
+            .name_template
+            .as_deref()
+            .unwrap_or(DEFAULT_NAME_TEMPLATE),
+        schemas: oas
+            .components
+            .as_mut()
+            .map(|components| std::mem::take(&mut components.schemas))
+            .unwrap_or_default(),
+        hoisted: HoistedSchemas::new(),
+    };
+
+    for (path, item) in oas.paths.paths.iter_mut() {
+        let item = match item {
+            ReferenceOr::Item(item) => item,
+            ReferenceOr::Reference { .. } => continue,
+        };
+        for (method, operation) in operation_slots_mut(item) {
+            if let Some(operation) = operation {
+                let pointer = JsonPointer::path(path).child(method);
+                hoister.hoist_operation(operation, &fallback_operation_id(method, path), pointer);
+            }
+        }
+    }
+
+    if !hoister.schemas.is_empty() {
+        oas.components.get_or_insert_with(Default::default).schemas = hoister.schemas;
+    }
+    hoister.hoisted
+}
+
+struct Hoister<'a> {
+    template: &'a str,
+    /// The schemas of the input, including the ones moved there so far
+    schemas: IndexMap<String, ReferenceOr<Schema>>,
+    hoisted: HoistedSchemas,
+}
+
+impl Hoister<'_> {
+    fn hoist_operation(
+        &mut self,
+        operation: &mut Operation,
+        fallback_id: &str,
+        pointer: JsonPointer,
+    ) {
+        let operation_id = operation
+            .operation_id
+            .clone()
+            .unwrap_or_else(|| fallback_id.to_string());
+
+        if let Some(ReferenceOr::Item(request_body)) = &mut operation.request_body {
+            let pointer = pointer.child("requestBody").child("content");
+            for (media_type, content) in request_body.content.iter_mut() {
+                if let Some(schema) = &mut content.schema {
+                    let pointer = pointer.child(media_type.as_str()).child("schema");
+                    self.hoist(schema, &operation_id, "Request", pointer);
+                }
+            }
+        }
+
+        let responses = operation
+            .responses
+            .default
+            .iter_mut()
+            .map(|response| ("default".to_string(), "Default".to_string(), response))
+            .chain(
+                operation
+                    .responses
+                    .responses
+                    .iter_mut()
+                    .map(|(code, response)| (code.to_string(), code.to_string(), response)),
+            );
+        for (key, name, response) in responses {
+            if let ReferenceOr::Item(Response { content, .. }) = response {
+                let pointer = pointer.child("responses").child(key).child("content");
+                for (media_type, content) in content.iter_mut() {
+                    if let Some(schema) = &mut content.schema {
+                        let pointer = pointer.child(media_type.as_str()).child("schema");
+                        let role = format!("Response{}", name);
+                        self.hoist(schema, &operation_id, &role, pointer);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Move the schema into the components if it is an inline object, or the items of the
+    /// schema if it is an array of inline objects
+    fn hoist(
+        &mut self,
+        schema: &mut ReferenceOr<Schema>,
+        operation_id: &str,
+        role: &str,
+        pointer: JsonPointer,
+    ) {
+        let item = match schema {
+            ReferenceOr::Item(item) => item,
+            ReferenceOr::Reference { .. } => return,
+        };
+        if is_inline_object(item) {
+            let reference = self.place(item.clone(), operation_id, role, pointer);
+            *schema = ReferenceOr::Reference { reference };
+        } else if let SchemaKind::Type(Type::Array(array)) = &mut item.schema_kind {
+            if let Some(ReferenceOr::Item(items)) = &array.items {
+                if is_inline_object(items) {
+                    let role = format!("{}Item", role);
+                    let reference = self.place(
+                        (**items).clone(),
+                        operation_id,
+                        &role,
+                        pointer.child("items"),
+                    );
+                    array.items = Some(ReferenceOr::Reference { reference });
+                }
+            }
+        }
+    }
+
+    /// Add the schema to the components, unless an identical schema is already there, and
+    /// return the reference to it
+    fn place(
+        &mut self,
+        schema: Schema,
+        operation_id: &str,
+        role: &str,
+        pointer: JsonPointer,
+    ) -> String {
+        let schema = ReferenceOr::Item(schema);
+        if let Some(name) = self
+            .schemas
+            .iter()
+            .find(|(_, existing)| components_equal(*existing, &schema))
+            .map(|(name, _)| name.clone())
+        {
+            return JsonPointer::component("schemas", &name).to_string();
+        }
+
+        let base = schema_name(self.template, operation_id, role);
+        let mut name = base.clone();
+        let mut suffix = 2;
+        while self.schemas.contains_key(&name) {
+            name = format!("{}{}", base, suffix);
+            suffix += 1;
+        }
+        self.schemas.insert(name.clone(), schema);
+        self.hoisted.insert(name.clone(), pointer);
+        JsonPointer::component("schemas", &name).to_string()
+    }
+}
+
+/// An object schema with properties of its own. Empty objects and compositions such as `allOf`
+/// are left where they are.
+fn is_inline_object(schema: &Schema) -> bool {
+    match &schema.schema_kind {
+        SchemaKind::Type(Type::Object(object)) => !object.properties.is_empty(),
+        _ => false,
+    }
+}
+
+/// Fill in the template. Characters that are not allowed in component names are dropped.
+fn schema_name(template: &str, operation_id: &str, role: &str) -> String {
+    template
+        .replace("{operationId}", operation_id)
+        .replace("{OperationId}", &capitalize(operation_id))
+        .replace("{role}", role)
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        .collect()
+}
+
+/// `get` and `/users/{id}` become `getUsersId`
+fn fallback_operation_id(method: &str, path: &str) -> String {
+    let words = path
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(capitalize);
+    std::iter::once(method.to_string()).chain(words).collect()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -1,10 +1,21 @@
 //! Info merging logic
 
-use crate::data::{MergeInput, SingleMergeInput};
+use crate::data::{
+    DescriptionOptions, InfoConfiguration, InfoFieldSource, MergeInput, MergeOptions,
+    SingleMergeInput, VersionStrategy,
+};
+use crate::merge::extensions::ExtensionMerger;
+use crate::merge::json_pointer::JsonPointer;
 use openapiv3::Info;
+use sha2::{Digest, Sha256};
+use std::time::UNIX_EPOCH;
 
 /// Merge info objects from all inputs
-pub fn merge_infos(inputs: &MergeInput) -> Info {
+pub fn merge_infos(
+    inputs: &MergeInput,
+    options: &MergeOptions,
+    extension_merger: &mut ExtensionMerger,
+) -> Info {
     if inputs.is_empty() {
         return Info {
             title: "Merged API".to_string(),
@@ -20,6 +31,16 @@ pub fn merge_infos(inputs: &MergeInput) -> Info {
     // Start with first input's info
     let mut final_info = inputs[0].oas.info.clone();
 
+    // Merge the info extensions of the remaining inputs
+    for (input_index, input) in inputs.iter().enumerate().skip(1) {
+        extension_merger.merge(
+            &mut final_info.extensions,
+            &input.oas.info.extensions,
+            &JsonPointer::from_segments(["info"]),
+            input_index,
+        );
+    }
+
     // Collect descriptions to append
     let mut appended_descriptions = Vec::new();
 
@@ -38,9 +59,218 @@ pub fn merge_infos(inputs: &MergeInput) -> Info {
         final_info.description = Some(appended_descriptions.join("\n\n"));
     }
 
+    final_info.description =
+        compose_description(final_info.description.take(), inputs, &options.description);
+
+    if let Some(info_config) = &options.info {
+        apply_info_configuration(&mut final_info, inputs, info_config);
+    }
+
     final_info
 }
 
+fn apply_info_configuration(info: &mut Info, inputs: &MergeInput, info_config: &InfoConfiguration) {
+    if let Some(title) = &info_config.title {
+        info.title = title.clone();
+    }
+
+    info.version = match &info_config.version {
+        Some(version) => version.clone(),
+        None => merge_versions(inputs, info_config.version_strategy.unwrap_or_default()),
+    };
+
+    if let Some(terms_of_service) = &info_config.terms_of_service {
+        info.terms_of_service = Some(terms_of_service.clone());
+    }
+
+    let source = info_config.contact_and_license.unwrap_or_default();
+    info.contact = match &info_config.contact {
+        Some(contact) => Some(contact.clone()),
+        None => select_field(inputs, source, |info| info.contact.as_ref()),
+    };
+    info.license = match &info_config.license {
+        Some(license) => Some(license.clone()),
+        None => select_field(inputs, source, |info| info.license.as_ref()),
+    };
+}
+
+fn select_field<T: Clone>(
+    inputs: &MergeInput,
+    source: InfoFieldSource,
+    field: impl Fn(&Info) -> Option<&T>,
+) -> Option<T> {
+    match source {
+        InfoFieldSource::First => field(&inputs[0].oas.info).cloned(),
+        InfoFieldSource::FirstDefined => inputs
+            .iter()
+            .find_map(|input| field(&input.oas.info))
+            .cloned(),
+        InfoFieldSource::Omit => None,
+    }
+}
+
+fn merge_versions(inputs: &MergeInput, strategy: VersionStrategy) -> String {
+    let first_version = inputs[0].oas.info.version.clone();
+
+    match strategy {
+        VersionStrategy::First => first_version,
+        VersionStrategy::HighestSemver => inputs
+            .iter()
+            .filter_map(|input| {
+                let version = &input.oas.info.version;
+                semver::Version::parse(version.trim_start_matches('v'))
+                    .ok()
+                    .map(|parsed| (parsed, version))
+            })
+            .max_by(|(x, _), (y, _)| x.cmp(y))
+            .map(|(_, version)| version.clone())
+            .unwrap_or(first_version),
+        VersionStrategy::Hash => hash_inputs(inputs),
+        // The date comes from the inputs rather than the clock, so that the same inputs always
+        // give the same output
+        VersionStrategy::Date => match inputs.iter().filter_map(|input| input.modified).max() {
+            Some(modified) => {
+                let days_since_epoch = modified
+                    .duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_secs() / 86_400)
+                    .unwrap_or(0);
+                let (year, month, day) = civil_from_days(days_since_epoch as i64);
+                format!("{:04}.{:02}.{:02}", year, month, day)
+            }
+            None => hash_inputs(inputs),
+        },
+    }
+}
+
+/// A short hash of the content of all inputs
+fn hash_inputs(inputs: &MergeInput) -> String {
+    let mut hasher = Sha256::new();
+    for input in inputs {
+        hasher.update(serde_json::to_vec(&input.oas).unwrap_or_default());
+    }
+    let digest = hasher.finalize();
+    digest
+        .iter()
+        .take(6)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Convert days since 1970-01-01 into a (year, month, day) date in the Gregorian calendar
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Place the preamble, table of contents and postamble around the merged description
+fn compose_description(
+    description: Option<String>,
+    inputs: &MergeInput,
+    description_options: &DescriptionOptions,
+) -> Option<String> {
+    let mut sections: Vec<String> = Vec::new();
+
+    if let Some(preamble) = &description_options.preamble {
+        sections.push(preamble.trim_end().to_string());
+    }
+    if description_options.table_of_contents {
+        if let Some(table_of_contents) = table_of_contents(inputs) {
+            sections.push(table_of_contents);
+        }
+    }
+    if let Some(description) = description {
+        sections.push(description);
+    }
+    if let Some(postamble) = &description_options.postamble {
+        sections.push(postamble.trim_end().to_string());
+    }
+
+    if sections.is_empty() {
+        None
+    } else {
+        Some(sections.join("\n\n"))
+    }
+}
+
+/// A Markdown list that links to the title of every appended description
+fn table_of_contents(inputs: &MergeInput) -> Option<String> {
+    let titles: Vec<(u8, &str)> = inputs
+        .iter()
+        .filter(|input| input.oas.info.description.is_some())
+        .filter_map(|input| input.description.as_ref())
+        .filter(|desc_config| desc_config.append)
+        .filter_map(|desc_config| desc_config.title.as_ref())
+        .map(|title| (title.heading_level.unwrap_or(1), title.value.as_str()))
+        .collect();
+
+    let top_level = titles.iter().map(|(level, _)| *level).min()?;
+    let entries: Vec<String> = titles
+        .iter()
+        .map(|(level, value)| {
+            format!(
+                "{}- [{}](#{})",
+                "  ".repeat((level - top_level) as usize),
+                value,
+                heading_anchor(value)
+            )
+        })
+        .collect();
+
+    Some(entries.join("\n"))
+}
+
+/// The anchor that Markdown renderers generate for a heading
+fn heading_anchor(heading: &str) -> String {
+    heading
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Push every ATX heading in the Markdown down by the given number of levels, to a maximum of
+/// h6. Lines inside fenced code blocks are left alone.
+fn shift_headings(markdown: &str, levels: u8) -> String {
+    let mut in_code_block = false;
+
+    markdown
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                in_code_block = !in_code_block;
+                return line.to_string();
+            }
+
+            let hashes = trimmed.chars().take_while(|c| *c == '#').count();
+            let is_heading = !in_code_block
+                && (1..=6).contains(&hashes)
+                && trimmed[hashes..].chars().next().is_none_or(|c| c == ' ');
+            if !is_heading {
+                return line.to_string();
+            }
+
+            let new_level = (hashes + levels as usize).min(6);
+            format!("{}{}", "#".repeat(new_level), &trimmed[hashes..])
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 fn get_info_description_with_heading(input: &SingleMergeInput) -> Option<String> {
     let description = input.oas.info.description.as_ref()?;
     let trimmed_description = description.trim_end();
@@ -55,6 +285,34 @@ fn get_info_description_with_heading(input: &SingleMergeInput) -> Option<String>
 
     Some(format!(
         "{} {}\n\n{}",
-        heading, title.value, trimmed_description
+        heading,
+        title.value,
+        shift_headings(trimmed_description, heading_level)
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::civil_from_days;
+
+    #[test]
+    fn civil_from_days_converts_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(10_957), (2000, 1, 1));
+        assert_eq!(civil_from_days(20_742), (2026, 10, 16));
+    }
+
+    #[test]
+    fn civil_from_days_handles_leap_days() {
+        // 2000 is a leap year because it is divisible by 400
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(11_017), (2000, 3, 1));
+        assert_eq!(civil_from_days(19_416), (2023, 2, 28));
+        assert_eq!(civil_from_days(19_417), (2023, 3, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+        // 2100 is not a leap year because it is divisible by 100
+        assert_eq!(civil_from_days(47_540), (2100, 2, 28));
+        assert_eq!(civil_from_days(47_541), (2100, 3, 1));
+    }
+}
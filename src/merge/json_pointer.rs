@@ -0,0 +1,252 @@
+//! JSON pointers into OpenAPI documents, as described in RFC 6901
+
+use serde_json::Value as JsonValue;
+use std::fmt;
+
+/// A pointer to a location in a document, stored as its unescaped segments.
+///
+/// Pointers are written in their URI fragment form, e.g. `#/paths/~1users~1{id}/get`, where
+/// `~` and `/` inside a segment are escaped as `~0` and `~1`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct JsonPointer {
+    segments: Vec<String>,
+}
+
+impl JsonPointer {
+    /// The pointer to the whole document, `#`
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    /// Build a pointer from unescaped segments
+    pub fn from_segments<I, S>(segments: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            segments: segments.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// The pointer to a path item, e.g. `#/paths/~1users`
+    pub fn path(path: &str) -> Self {
+        Self::from_segments(["paths", path])
+    }
+
+    /// The pointer to a component, e.g. `#/components/schemas/User`
+    pub fn component(kind: &str, name: &str) -> Self {
+        Self::from_segments(["components", kind, name])
+    }
+
+    /// Parse a local reference such as `#/components/schemas/User`. The fragment is
+    /// percent-decoded before its segments are unescaped. Returns `None` for references to
+    /// other documents and for fragments that are not valid pointers.
+    pub fn parse(reference: &str) -> Option<Self> {
+        let fragment = percent_decode(reference.strip_prefix('#')?)?;
+        if fragment.is_empty() {
+            return Some(Self::root());
+        }
+
+        let segments = fragment
+            .strip_prefix('/')?
+            .split('/')
+            .map(unescape_segment)
+            .collect::<Option<Vec<String>>>()?;
+        Some(Self { segments })
+    }
+
+    /// The unescaped segments of this pointer
+    pub fn segments(&self) -> &[String] {
+        &self.segments
+    }
+
+    /// A pointer to a child of this location
+    pub fn child(&self, segment: impl Into<String>) -> Self {
+        let mut segments = self.segments.clone();
+        segments.push(segment.into());
+        Self { segments }
+    }
+
+    /// Whether this pointer is the given location or somewhere inside it
+    pub fn starts_with(&self, prefix: &JsonPointer) -> bool {
+        self.segments.starts_with(&prefix.segments)
+    }
+
+    /// Move this pointer from inside one location to the same place inside another.
+    /// Returns `None` if this pointer is not inside `from`.
+    pub fn rebase(&self, from: &JsonPointer, to: &JsonPointer) -> Option<Self> {
+        let rest = self.segments.strip_prefix(from.segments.as_slice())?;
+        let mut segments = to.segments.clone();
+        segments.extend(rest.iter().cloned());
+        Some(Self { segments })
+    }
+
+    /// Find the value this pointer refers to in a document
+    pub fn resolve<'a>(&self, document: &'a JsonValue) -> Option<&'a JsonValue> {
+        self.segments
+            .iter()
+            .try_fold(document, |value, segment| match value {
+                JsonValue::Object(fields) => fields.get(segment),
+                JsonValue::Array(items) => items.get(segment.parse::<usize>().ok()?),
+                _ => None,
+            })
+    }
+
+    /// Find the value this pointer refers to in a document, for modification
+    pub fn resolve_mut<'a>(&self, document: &'a mut JsonValue) -> Option<&'a mut JsonValue> {
+        self.segments
+            .iter()
+            .try_fold(document, |value, segment| match value {
+                JsonValue::Object(fields) => fields.get_mut(segment),
+                JsonValue::Array(items) => items.get_mut(segment.parse::<usize>().ok()?),
+                _ => None,
+            })
+    }
+}
+
+impl fmt::Display for JsonPointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#")?;
+        for segment in &self.segments {
+            write!(f, "/{}", escape_segment(segment))?;
+        }
+        Ok(())
+    }
+}
+
+/// Escape a single segment of a JSON pointer
+pub fn escape_segment(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
+/// Unescape a single segment of a JSON pointer. Returns `None` if the segment contains a `~`
+/// that is not followed by `0` or `1`.
+pub fn unescape_segment(segment: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(segment.len());
+    let mut chars = segment.chars();
+    while let Some(c) = chars.next() {
+        if c == '~' {
+            match chars.next() {
+                Some('0') => unescaped.push('~'),
+                Some('1') => unescaped.push('/'),
+                _ => return None,
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+    Some(unescaped)
+}
+
+/// Decode `%XX` sequences in a URI fragment. Returns `None` if a sequence is malformed or the
+/// decoded bytes are not UTF-8.
+fn percent_decode(fragment: &str) -> Option<String> {
+    if !fragment.contains('%') {
+        return Some(fragment.to_string());
+    }
+
+    let bytes = fragment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            let hex = fragment.get(index + 1..index + 3)?;
+            if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JsonPointer;
+
+    fn pointer(segments: &[&str]) -> JsonPointer {
+        JsonPointer::from_segments(segments.iter().copied())
+    }
+
+    #[test]
+    fn parse_unescapes_segments() {
+        assert_eq!(JsonPointer::parse("#"), Some(JsonPointer::root()));
+        assert_eq!(
+            JsonPointer::parse("#/paths/~1users~1{id}/get"),
+            Some(pointer(&["paths", "/users/{id}", "get"]))
+        );
+        assert_eq!(
+            JsonPointer::parse("#/components/schemas/a~0b~01"),
+            Some(pointer(&["components", "schemas", "a~b~1"]))
+        );
+        assert_eq!(JsonPointer::parse("#/a/"), Some(pointer(&["a", ""])));
+    }
+
+    #[test]
+    fn parse_percent_decodes_before_unescaping() {
+        assert_eq!(
+            JsonPointer::parse("#/paths/~1users~1%7Bid%7D"),
+            Some(pointer(&["paths", "/users/{id}"]))
+        );
+        assert_eq!(
+            JsonPointer::parse("#/components/schemas/caf%C3%A9"),
+            Some(pointer(&["components", "schemas", "café"]))
+        );
+        assert_eq!(JsonPointer::parse("#/a%25b"), Some(pointer(&["a%b"])));
+        assert_eq!(JsonPointer::parse("#/a%7E1b"), Some(pointer(&["a/b"])));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_references() {
+        assert_eq!(JsonPointer::parse("#/a~2b"), None);
+        assert_eq!(JsonPointer::parse("#/a~"), None);
+        assert_eq!(JsonPointer::parse("#/a%2"), None);
+        assert_eq!(JsonPointer::parse("#/a%zz"), None);
+        assert_eq!(JsonPointer::parse("#/a%FF"), None);
+        assert_eq!(JsonPointer::parse("#a"), None);
+        assert_eq!(JsonPointer::parse("other.yaml#/a"), None);
+    }
+
+    #[test]
+    fn display_escapes_segments() {
+        assert_eq!(JsonPointer::root().to_string(), "#");
+        assert_eq!(
+            JsonPointer::path("/users/{id}").child("get").to_string(),
+            "#/paths/~1users~1{id}/get"
+        );
+        assert_eq!(pointer(&["a~/b"]).to_string(), "#/a~0~1b");
+
+        let original = pointer(&["paths", "/a~b/", "x"]);
+        assert_eq!(JsonPointer::parse(&original.to_string()), Some(original));
+    }
+
+    #[test]
+    fn starts_with_compares_whole_segments() {
+        let schema = JsonPointer::component("schemas", "User");
+        assert!(schema.starts_with(&schema));
+        assert!(schema.child("properties").starts_with(&schema));
+        assert!(schema.starts_with(&JsonPointer::root()));
+        assert!(!JsonPointer::component("schemas", "UserList").starts_with(&schema));
+        assert!(!schema.starts_with(&schema.child("properties")));
+    }
+
+    #[test]
+    fn rebase_moves_the_rest_of_the_pointer() {
+        let from = JsonPointer::component("schemas", "User");
+        let to = JsonPointer::component("schemas", "User2");
+        assert_eq!(from.rebase(&from, &to), Some(to.clone()));
+        assert_eq!(
+            from.child("properties").child("id").rebase(&from, &to),
+            Some(to.child("properties").child("id"))
+        );
+        assert_eq!(
+            JsonPointer::component("schemas", "UserList").rebase(&from, &to),
+            None
+        );
+    }
+}
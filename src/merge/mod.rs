@@ -1,56 +1,134 @@
 //! OpenAPI merging logic
 
 pub mod component_equivalence;
+pub mod dangling_references;
+pub mod dereference;
 pub mod dispute;
 pub mod extensions;
+pub mod hoisting;
 pub mod info;
+pub mod json_pointer;
 pub mod operation_selection;
+pub mod ordering;
+pub mod origins;
+pub mod output_selection;
 pub mod paths_components;
+pub mod provenance;
 pub mod reference_walker;
 pub mod tags;
 
-use crate::data::{ErrorMergeResult, ErrorType, MergeInput};
+use crate::data::{
+    DanglingReference, DanglingReferenceBehaviour, MergeInput, MergeOptions, SuccessfulMergeResult,
+};
+use crate::diagnostics::{
+    Diagnostic, DANGLING_REFERENCE, EXTENSION_CONFLICT, EXTERNAL_DOCS_IGNORED,
+    OPENAPI_VERSION_MISMATCH, SERVERS_DISCARDED,
+};
+use crate::error::{ErrorLocation, ErrorOccurrence, MergeError};
+use json_pointer::JsonPointer;
 use openapiv3::OpenAPI;
 
 /// Merge multiple OpenAPI files into a single file
 pub fn merge(
     inputs: &MergeInput,
-    openapi_version: Option<&str>,
-) -> Result<OpenAPI, ErrorMergeResult> {
+    options: &MergeOptions,
+) -> Result<SuccessfulMergeResult, MergeError> {
     if inputs.is_empty() {
-        return Err(ErrorMergeResult {
-            error_type: ErrorType::NoInputs,
-            message: "You must provide at least one OAS file as an input.".to_string(),
-        });
+        return Err(MergeError::NoInputs);
     }
 
     // Determine OpenAPI version
-    let version = if let Some(version) = openapi_version {
-        version.to_string()
+    let version = if let Some(version) = &options.openapi_version {
+        version.clone()
     } else {
         // Use version from first input
         inputs[0].oas.openapi.clone()
     };
 
+    let mut diagnostics = Vec::new();
+    for (input_index, input) in inputs.iter().enumerate().skip(1) {
+        if input.oas.openapi != inputs[0].oas.openapi {
+            diagnostics.push(Diagnostic::warning(
+                OPENAPI_VERSION_MISMATCH,
+                format!(
+                    "This input is OpenAPI {} but the first input is OpenAPI {}",
+                    input.oas.openapi, inputs[0].oas.openapi
+                ),
+                ErrorLocation::for_input(input_index, input)
+                    .with_input_pointer(JsonPointer::from_segments(["openapi"])),
+            ));
+        }
+    }
+
+    let mut extension_merger = extensions::ExtensionMerger::new(options.extensions.as_ref());
+
     // Merge paths and components
-    let (paths, components) = paths_components::merge_paths_and_components(inputs)?;
+    let (paths, components, mut origins) = paths_components::merge_paths_and_components(
+        inputs,
+        options,
+        &mut extension_merger,
+        &mut diagnostics,
+    )?;
 
     // Merge other parts
-    let info = info::merge_infos(inputs);
-    let tags = tags::merge_tags(inputs).unwrap_or_default();
-    let servers = inputs
-        .iter()
-        .find(|input| !input.oas.servers.is_empty())
-        .map(|input| input.oas.servers.clone())
+    let info = info::merge_infos(inputs, options, &mut extension_merger);
+    let tags = tags::merge_tags(inputs, options, &mut extension_merger, &mut diagnostics)
         .unwrap_or_default();
-    let external_docs = inputs
+    let servers_input = inputs
         .iter()
-        .find_map(|input| input.oas.external_docs.as_ref())
-        .cloned();
-    let security = inputs
+        .position(|input| !input.oas.servers.is_empty());
+    let servers = servers_input
+        .map(|index| inputs[index].oas.servers.clone())
+        .unwrap_or_default();
+    let external_docs_input = inputs
         .iter()
-        .find_map(|input| input.oas.security.as_ref())
-        .cloned();
+        .position(|input| input.oas.external_docs.is_some());
+    let external_docs =
+        external_docs_input.and_then(|index| inputs[index].oas.external_docs.clone());
+    let security_input = inputs.iter().position(|input| input.oas.security.is_some());
+    let security = security_input.and_then(|index| inputs[index].oas.security.clone());
+    let root_origins = [
+        ("servers", servers_input),
+        ("externalDocs", external_docs_input),
+        ("security", security_input),
+    ];
+    for (field, input_index) in root_origins {
+        if let Some(input_index) = input_index {
+            let pointer = JsonPointer::from_segments([field]);
+            origins.insert(
+                pointer.clone(),
+                origins::Origin {
+                    input_index,
+                    original_pointer: pointer,
+                },
+            );
+        }
+    }
+
+    for (input_index, input) in inputs.iter().enumerate() {
+        if !input.oas.servers.is_empty() && input.oas.servers != servers {
+            diagnostics.push(Diagnostic::warning(
+                SERVERS_DISCARDED,
+                format!(
+                    "The servers of this input were discarded in favour of those of input {}",
+                    servers_input.unwrap_or_default()
+                ),
+                ErrorLocation::for_input(input_index, input)
+                    .with_input_pointer(JsonPointer::from_segments(["servers"])),
+            ));
+        }
+        if input.oas.external_docs.is_some() && input.oas.external_docs != external_docs {
+            diagnostics.push(Diagnostic::warning(
+                EXTERNAL_DOCS_IGNORED,
+                format!(
+                    "The externalDocs of this input were ignored in favour of those of input {}",
+                    external_docs_input.unwrap_or_default()
+                ),
+                ErrorLocation::for_input(input_index, input)
+                    .with_input_pointer(JsonPointer::from_segments(["externalDocs"])),
+            ));
+        }
+    }
 
     // Build output
     let mut output = OpenAPI {
@@ -66,7 +144,97 @@ pub fn merge(
     };
 
     // Merge extensions
-    extensions::merge_extensions(&mut output, inputs);
+    extensions::merge_extensions(&mut output, inputs, &mut extension_merger);
+    for conflict in extension_merger.finish(inputs)? {
+        diagnostics.push(Diagnostic::warning(
+            EXTENSION_CONFLICT,
+            format!(
+                "The value of '{}' conflicts with the value from a previous input and was resolved with {}",
+                conflict.key,
+                conflict.strategy.name()
+            ),
+            ErrorLocation::for_input(conflict.input_index, &inputs[conflict.input_index])
+                .with_output_pointer(conflict.location.clone()),
+        ));
+    }
+
+    // The generated tag groups replace any that were defined by the inputs
+    if options.tag_groups {
+        output
+            .extensions
+            .insert("x-tagGroups".to_string(), tags::merge_tag_groups(inputs));
+    }
+
+    if options.annotate_sources {
+        provenance::annotate_merge_sources(&mut output, inputs, &origins)?;
+    }
 
-    Ok(output)
+    let tag_positions = ordering::apply_ordering(&mut output, &options.ordering);
+    for diagnostic in &mut diagnostics {
+        if let Some(pointer) = &mut diagnostic.location.output_pointer {
+            if let Some(moved) = moved_tag_pointer(pointer, &tag_positions) {
+                *pointer = moved;
+            }
+        }
+    }
+
+    // Check that every reference still points at something
+    let dangling_references = dangling_references::find_dangling_references(&output, &origins);
+    if !dangling_references.is_empty()
+        && options.dangling_references == DanglingReferenceBehaviour::Error
+    {
+        let references = dangling_references
+            .iter()
+            .map(|dangling| ErrorOccurrence {
+                value: dangling.reference.clone(),
+                location: dangling_reference_location(inputs, dangling),
+            })
+            .collect();
+        return Err(MergeError::DanglingReference { references });
+    }
+
+    for dangling in &dangling_references {
+        diagnostics.push(Diagnostic::warning(
+            DANGLING_REFERENCE,
+            format!("The reference '{}' does not resolve", dangling.reference),
+            dangling_reference_location(inputs, dangling),
+        ));
+    }
+
+    diagnostics.retain(|diagnostic| {
+        !options
+            .suppressed_warnings
+            .iter()
+            .any(|code| code == diagnostic.code)
+    });
+
+    Ok(SuccessfulMergeResult {
+        output,
+        dangling_references,
+        diagnostics,
+        origins,
+    })
+}
+
+fn dangling_reference_location(inputs: &MergeInput, dangling: &DanglingReference) -> ErrorLocation {
+    let mut location = dangling
+        .input_index
+        .map(|input_index| ErrorLocation::for_input(input_index, &inputs[input_index]))
+        .unwrap_or_default()
+        .with_output_pointer(dangling.location.clone());
+    location.input_pointer = dangling.input_pointer.clone();
+    location
+}
+
+/// The new location of a location under `#/tags` after the tags were reordered
+fn moved_tag_pointer(pointer: &JsonPointer, tag_positions: &[usize]) -> Option<JsonPointer> {
+    match pointer.segments() {
+        [tags, index, ..] if tags == "tags" => {
+            let new_index = tag_positions.get(index.parse::<usize>().ok()?)?;
+            let from = JsonPointer::from_segments([tags.clone(), index.clone()]);
+            let to = JsonPointer::from_segments([tags.clone(), new_index.to_string()]);
+            pointer.rebase(&from, &to)
+        }
+        _ => None,
+    }
 }
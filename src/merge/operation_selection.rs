@@ -1,7 +1,7 @@
 //! Operation selection logic
 
 use crate::data::OperationSelection;
-use openapiv3::{OpenAPI, Operation, ReferenceOr};
+use openapiv3::{OpenAPI, Operation, PathItem, ReferenceOr};
 
 /// Run operation selection filtering
 pub fn run_operation_selection(
@@ -29,6 +29,29 @@ pub fn run_operation_selection(
     oas
 }
 
+/// Iterate mutably over the operation of every method of a path item, with the name of the
+/// method, whether or not the operation is defined
+pub fn operation_slots_mut(
+    item: &mut PathItem,
+) -> impl Iterator<Item = (&'static str, &mut Option<Operation>)> {
+    [
+        ("get", &mut item.get),
+        ("put", &mut item.put),
+        ("post", &mut item.post),
+        ("delete", &mut item.delete),
+        ("options", &mut item.options),
+        ("head", &mut item.head),
+        ("patch", &mut item.patch),
+        ("trace", &mut item.trace),
+    ]
+    .into_iter()
+}
+
+/// Iterate mutably over every operation that is defined on a path item
+pub fn operations_mut(item: &mut PathItem) -> impl Iterator<Item = &mut Operation> {
+    operation_slots_mut(item).filter_map(|(_, operation)| operation.as_mut())
+}
+
 fn operation_contains_any_tag(operation: &Operation, tags: &[String]) -> bool {
     operation.tags.iter().any(|tag| tags.contains(tag))
 }
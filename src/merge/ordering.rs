@@ -0,0 +1,255 @@
+//! Canonical ordering of the merged output
+
+use crate::data::{FieldOrder, OrderingConfiguration, PathOrdering};
+use crate::merge::operation_selection::operations_mut;
+use indexmap::IndexMap;
+use openapiv3::{OpenAPI, ReferenceOr, StatusCode};
+use std::cmp::Ordering;
+
+/// Sort the output as configured. Returns the new position of every tag definition, indexed by
+/// its old position, so that locations that refer to tags can be updated.
+pub fn apply_ordering(oas: &mut OpenAPI, ordering: &OrderingConfiguration) -> Vec<usize> {
+    match ordering.paths.unwrap_or_default() {
+        PathOrdering::Input => {}
+        PathOrdering::Lexicographic => oas.paths.paths.sort_keys(),
+        PathOrdering::Specificity => oas
+            .paths
+            .paths
+            .sort_by(|a, _, b, _| compare_specificity(a, b)),
+    }
+
+    if ordering.components.unwrap_or(false) {
+        if let Some(components) = &mut oas.components {
+            components.security_schemes.sort_keys();
+            components.responses.sort_keys();
+            components.parameters.sort_keys();
+            components.examples.sort_keys();
+            components.request_bodies.sort_keys();
+            components.headers.sort_keys();
+            components.schemas.sort_keys();
+            components.links.sort_keys();
+            components.callbacks.sort_keys();
+        }
+    }
+
+    let mut tag_positions: Vec<usize> = (0..oas.tags.len()).collect();
+    if ordering.tags.unwrap_or(false) {
+        let mut tags: Vec<(usize, _)> = oas.tags.drain(..).enumerate().collect();
+        tags.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+        for (new_position, (old_position, tag)) in tags.into_iter().enumerate() {
+            tag_positions[old_position] = new_position;
+            oas.tags.push(tag);
+        }
+    }
+
+    if ordering.field_order.unwrap_or_default() == FieldOrder::Recommended {
+        sort_fields(oas);
+    }
+
+    tag_positions
+}
+
+/// Compare two path templates segment by segment. Literal segments sort before templated ones
+/// and a path sorts before the paths below it.
+fn compare_specificity(a: &str, b: &str) -> Ordering {
+    let mut a_segments = a.split('/');
+    let mut b_segments = b.split('/');
+    loop {
+        match (a_segments.next(), b_segments.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) => {
+                let ordering = is_templated(a).cmp(&is_templated(b)).then_with(|| a.cmp(b));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+fn is_templated(segment: &str) -> bool {
+    segment.contains('{')
+}
+
+/// Sort responses by status code, with each range after the codes it covers, and extensions by
+/// name. Fixed fields are always written in the order of the specification.
+fn sort_fields(oas: &mut OpenAPI) {
+    oas.extensions.sort_keys();
+    oas.info.extensions.sort_keys();
+    oas.paths.extensions.sort_keys();
+    for tag in &mut oas.tags {
+        tag.extensions.sort_keys();
+    }
+
+    for item in oas.paths.paths.values_mut() {
+        if let ReferenceOr::Item(item) = item {
+            item.extensions.sort_keys();
+            for operation in operations_mut(item) {
+                operation.extensions.sort_keys();
+                operation.responses.extensions.sort_keys();
+                sort_responses(&mut operation.responses.responses);
+            }
+        }
+    }
+
+    if let Some(components) = &mut oas.components {
+        components.extensions.sort_keys();
+    }
+}
+
+fn sort_responses<T>(responses: &mut IndexMap<StatusCode, T>) {
+    responses.sort_by(|a, _, b, _| status_code_key(a).cmp(&status_code_key(b)));
+}
+
+/// `2XX` sorts after `299`, and before `300`
+fn status_code_key(code: &StatusCode) -> (u16, bool, u16) {
+    match code {
+        StatusCode::Code(code) => (code / 100, false, *code),
+        StatusCode::Range(range) => (*range, true, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn document() -> OpenAPI {
+        serde_json::from_value(json!({
+            "openapi": "3.0.3",
+            "info": { "title": "Shop", "version": "1" },
+            "x-zeta": 1,
+            "x-alpha": 2,
+            "tags": [{ "name": "Orders" }, { "name": "Admin" }, { "name": "Carts" }],
+            "paths": {
+                "/users/{id}": {},
+                "/users/me": {},
+                "/orders/{id}/items": {},
+                "/users": {},
+                "/orders/{id}": {
+                    "x-b": true,
+                    "x-a": true,
+                    "get": {
+                        "responses": {
+                            "default": { "description": "error" },
+                            "404": { "description": "not found" },
+                            "2XX": { "description": "ok" },
+                            "204": { "description": "no content" },
+                            "200": { "description": "ok" },
+                            "4XX": { "description": "client error" }
+                        }
+                    }
+                },
+                "/{tenant}/users": {},
+                "/users-admin": {}
+            }
+        }))
+        .unwrap()
+    }
+
+    fn ordered(paths: PathOrdering) -> Vec<String> {
+        let mut oas = document();
+        let ordering = OrderingConfiguration {
+            paths: Some(paths),
+            ..Default::default()
+        };
+        apply_ordering(&mut oas, &ordering);
+        oas.paths.paths.keys().cloned().collect()
+    }
+
+    #[test]
+    fn paths_keep_the_input_order_by_default() {
+        assert_eq!(
+            ordered(PathOrdering::Input),
+            [
+                "/users/{id}",
+                "/users/me",
+                "/orders/{id}/items",
+                "/users",
+                "/orders/{id}",
+                "/{tenant}/users",
+                "/users-admin"
+            ]
+        );
+    }
+
+    #[test]
+    fn paths_sort_lexicographically() {
+        assert_eq!(
+            ordered(PathOrdering::Lexicographic),
+            [
+                "/orders/{id}",
+                "/orders/{id}/items",
+                "/users",
+                "/users-admin",
+                "/users/me",
+                "/users/{id}",
+                "/{tenant}/users"
+            ]
+        );
+    }
+
+    #[test]
+    fn paths_sort_by_specificity() {
+        assert_eq!(
+            ordered(PathOrdering::Specificity),
+            [
+                "/orders/{id}",
+                "/orders/{id}/items",
+                "/users",
+                "/users/me",
+                "/users/{id}",
+                "/users-admin",
+                "/{tenant}/users"
+            ]
+        );
+        assert_eq!(compare_specificity("/a/b", "/a/{b}"), Ordering::Less);
+        assert_eq!(compare_specificity("/a/{b}", "/a/b/c"), Ordering::Greater);
+        assert_eq!(compare_specificity("/a", "/a/b"), Ordering::Less);
+        assert_eq!(compare_specificity("/{a}", "/b/c"), Ordering::Greater);
+        assert_eq!(compare_specificity("/a/{b}", "/a/{b}"), Ordering::Equal);
+    }
+
+    #[test]
+    fn tags_sort_by_name_and_report_their_new_positions() {
+        let mut oas = document();
+        let ordering = OrderingConfiguration {
+            tags: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(apply_ordering(&mut oas, &ordering), [2, 0, 1]);
+        let tags: Vec<&str> = oas.tags.iter().map(|tag| tag.name.as_str()).collect();
+        assert_eq!(tags, ["Admin", "Carts", "Orders"]);
+
+        let mut oas = document();
+        assert_eq!(
+            apply_ordering(&mut oas, &OrderingConfiguration::default()),
+            [0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn recommended_field_order_sorts_responses_and_extensions() {
+        let mut oas = document();
+        let ordering = OrderingConfiguration {
+            field_order: Some(FieldOrder::Recommended),
+            ..Default::default()
+        };
+        apply_ordering(&mut oas, &ordering);
+
+        let output = serde_json::to_value(&oas).unwrap();
+        let keys = |value: &serde_json::Value| -> Vec<String> {
+            value.as_object().unwrap().keys().cloned().collect()
+        };
+        let item = &output["paths"]["/orders/{id}"];
+        assert_eq!(keys(item), ["get", "x-a", "x-b"]);
+        assert_eq!(
+            keys(&item["get"]["responses"]),
+            ["default", "200", "204", "2XX", "404", "4XX"]
+        );
+        let root = keys(&output);
+        assert_eq!(root[root.len() - 2..], ["x-alpha", "x-zeta"]);
+    }
+}
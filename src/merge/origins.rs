@@ -0,0 +1,36 @@
+//! Tracking which input each part of the merged output came from
+
+use crate::merge::json_pointer::JsonPointer;
+use indexmap::IndexMap;
+
+/// Where a path item or component in the merged output came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    /// The index of the input in the configuration.
+    pub input_index: usize,
+    /// The location of the element in its input, before any renames or path modifications.
+    pub original_pointer: JsonPointer,
+}
+
+/// The origin of every path item and component in the merged output, keyed by its location in
+/// the output, e.g. `#/components/schemas/Item`
+pub type Origins = IndexMap<JsonPointer, Origin>;
+
+/// Translate a location in the output into the same location in the input it came from
+pub fn original_location(origins: &Origins, location: &JsonPointer) -> Option<Origin> {
+    let (pointer, origin) = containing_origin(origins, location)?;
+    Some(Origin {
+        input_index: origin.input_index,
+        original_pointer: location.rebase(pointer, &origin.original_pointer)?,
+    })
+}
+
+fn containing_origin<'a>(
+    origins: &'a Origins,
+    location: &JsonPointer,
+) -> Option<(&'a JsonPointer, &'a Origin)> {
+    origins
+        .iter()
+        .filter(|(pointer, _)| location.starts_with(pointer))
+        .max_by_key(|(pointer, _)| pointer.segments().len())
+}
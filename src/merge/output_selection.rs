@@ -0,0 +1,450 @@
+//! Selecting a named output from the merged result
+
+use crate::data::{Dereference, InfoOverride, OperationFilter, OutputConfiguration};
+use crate::error::{ErrorLocation, MergeError};
+use crate::merge::dereference::dereference;
+use crate::merge::json_pointer::JsonPointer;
+use crate::merge::operation_selection::{operation_slots_mut, operations_mut};
+use indexmap::IndexMap;
+use openapiv3::{OpenAPI, Operation, ReferenceOr};
+use serde_json::Value as JsonValue;
+use std::collections::{BTreeSet, HashSet};
+
+/// Produce a named output from the merged result: filter its operations, prune its components,
+/// override its info and inline its references
+pub fn select_output(
+    merged: &OpenAPI,
+    config: &OutputConfiguration,
+) -> Result<OpenAPI, MergeError> {
+    let mut output = merged.clone();
+
+    if let Some(filter) = &config.operation_filter {
+        filter_operations(&mut output, filter);
+    }
+
+    if config.prune_components.unwrap_or(false) {
+        output = prune_components(&output)?;
+    }
+
+    if let Some(info) = &config.info {
+        override_info(&mut output, info);
+    }
+
+    if let Some(mode) = config.dereference {
+        output = dereference(&output, mode == Dereference::DropComponents)?;
+    }
+
+    Ok(output)
+}
+
+fn filter_operations(oas: &mut OpenAPI, filter: &OperationFilter) {
+    let tags_before = used_tags(oas);
+
+    oas.paths.paths.retain(|path, item| {
+        let item = match item {
+            ReferenceOr::Item(item) => item,
+            ReferenceOr::Reference { .. } => return true,
+        };
+        if operations_mut(item).next().is_none() {
+            return true;
+        }
+
+        let path_extensions = item.extensions.clone();
+        for (_, operation) in operation_slots_mut(item) {
+            let keep = operation
+                .as_ref()
+                .is_some_and(|operation| keeps(filter, path, &path_extensions, operation));
+            if !keep {
+                *operation = None;
+            }
+        }
+        operations_mut(item).next().is_some()
+    });
+
+    // Tags whose operations were all filtered out go too, but unused tags are left alone
+    let tags_after = used_tags(oas);
+    let dropped: HashSet<String> = tags_before.difference(&tags_after).cloned().collect();
+    oas.tags.retain(|tag| !dropped.contains(&tag.name));
+    if let Some(JsonValue::Array(groups)) = oas.extensions.get_mut("x-tagGroups") {
+        for group in groups.iter_mut() {
+            if let Some(JsonValue::Array(tags)) = group.get_mut("tags") {
+                tags.retain(|tag| !tag.as_str().is_some_and(|tag| dropped.contains(tag)));
+            }
+        }
+        groups.retain(|group| {
+            group
+                .get("tags")
+                .and_then(JsonValue::as_array)
+                .is_none_or(|tags| !tags.is_empty())
+        });
+    }
+}
+
+fn keeps(
+    filter: &OperationFilter,
+    path: &str,
+    path_extensions: &IndexMap<String, JsonValue>,
+    operation: &Operation,
+) -> bool {
+    let has_tag = |tags: &[String]| operation.tags.iter().any(|tag| tags.contains(tag));
+    let under_path = |paths: &[String]| paths.iter().any(|prefix| is_under(path, prefix));
+    let sets_extension = |extensions: &[String]| {
+        extensions.iter().any(|extension| {
+            [
+                operation.extensions.get(extension),
+                path_extensions.get(extension),
+            ]
+            .into_iter()
+            .flatten()
+            .any(|value| !matches!(value, JsonValue::Null | JsonValue::Bool(false)))
+        })
+    };
+
+    let included = filter.include_tags.as_deref().is_none_or(has_tag)
+        && filter.include_paths.as_deref().is_none_or(under_path)
+        && filter
+            .include_extensions
+            .as_deref()
+            .is_none_or(sets_extension);
+    let excluded = filter.exclude_tags.as_deref().is_some_and(has_tag)
+        || filter.exclude_paths.as_deref().is_some_and(under_path)
+        || filter
+            .exclude_extensions
+            .as_deref()
+            .is_some_and(sets_extension);
+
+    included && !excluded
+}
+
+/// Whether the path is the prefix or below it, e.g. `/admin/users` is under `/admin`
+fn is_under(path: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/') || prefix.is_empty(),
+        None => false,
+    }
+}
+
+fn used_tags(oas: &mut OpenAPI) -> HashSet<String> {
+    oas.paths
+        .paths
+        .values_mut()
+        .filter_map(|item| match item {
+            ReferenceOr::Item(item) => Some(item),
+            ReferenceOr::Reference { .. } => None,
+        })
+        .flat_map(|item| {
+            operations_mut(item)
+                .flat_map(|operation| operation.tags.clone())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Drop every component that cannot be reached by following references from outside of the
+/// components. Security schemes are kept while a security requirement names them, and schemas
+/// while a discriminator mapping names them.
+pub(crate) fn prune_components(oas: &OpenAPI) -> Result<OpenAPI, MergeError> {
+    let serialization_error = |e: serde_json::Error| MergeError::Serialization {
+        message: e.to_string(),
+        location: ErrorLocation::default().into(),
+    };
+    let mut document = serde_json::to_value(oas).map_err(serialization_error)?;
+
+    let components = match document
+        .as_object_mut()
+        .and_then(|root| root.remove("components"))
+    {
+        Some(components) => components,
+        None => return Ok(oas.clone()),
+    };
+
+    let mut reachable = BTreeSet::new();
+    let mut pending = Vec::new();
+    collect_references(&document, &mut pending);
+    for name in security_scheme_names(oas) {
+        pending.push(JsonPointer::component("securitySchemes", &name));
+    }
+
+    document["components"] = components;
+    while let Some(pointer) = pending.pop() {
+        let component = match pointer.segments() {
+            [first, kind, name, ..] if first == "components" => JsonPointer::component(kind, name),
+            _ => continue,
+        };
+        if !reachable.insert(component.clone()) {
+            continue;
+        }
+        if let Some(value) = component.resolve(&document) {
+            collect_references(value, &mut pending);
+        }
+    }
+
+    if let Some(JsonValue::Object(kinds)) = document.get_mut("components") {
+        for (kind, entries) in kinds.iter_mut() {
+            if kind.starts_with("x-") {
+                continue;
+            }
+            if let JsonValue::Object(entries) = entries {
+                entries.retain(|name, _| reachable.contains(&JsonPointer::component(kind, name)));
+            }
+        }
+        kinds.retain(|kind, entries| {
+            kind.starts_with("x-")
+                || entries
+                    .as_object()
+                    .is_none_or(|entries| !entries.is_empty())
+        });
+    }
+
+    serde_json::from_value(document).map_err(serialization_error)
+}
+
+fn collect_references(value: &JsonValue, references: &mut Vec<JsonPointer>) {
+    match value {
+        JsonValue::Object(fields) => {
+            if let Some(JsonValue::String(reference)) = fields.get("$ref") {
+                references.extend(JsonPointer::parse(reference));
+            }
+            // A mapping value is either a reference or the name of a schema
+            if let Some(JsonValue::Object(mapping)) = fields
+                .get("discriminator")
+                .and_then(|discriminator| discriminator.get("mapping"))
+            {
+                for target in mapping.values().filter_map(JsonValue::as_str) {
+                    references.push(
+                        JsonPointer::parse(target)
+                            .unwrap_or_else(|| JsonPointer::component("schemas", target)),
+                    );
+                }
+            }
+            for child in fields.values() {
+                collect_references(child, references);
+            }
+        }
+        JsonValue::Array(items) => {
+            for child in items {
+                collect_references(child, references);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The names of the security schemes used by the root and operation security requirements
+fn security_scheme_names(oas: &OpenAPI) -> BTreeSet<String> {
+    let operations = oas
+        .paths
+        .paths
+        .values()
+        .filter_map(ReferenceOr::as_item)
+        .flat_map(|item| item.iter())
+        .map(|(_, operation)| operation.security.as_ref());
+
+    std::iter::once(oas.security.as_ref())
+        .chain(operations)
+        .flatten()
+        .flatten()
+        .flat_map(|requirement| requirement.keys().cloned())
+        .collect()
+}
+
+fn override_info(oas: &mut OpenAPI, info: &InfoOverride) {
+    if let Some(title) = &info.title {
+        oas.info.title = title.clone();
+    }
+    if let Some(version) = &info.version {
+        oas.info.version = version.clone();
+    }
+    if let Some(description) = &info.description {
+        oas.info.description = Some(description.clone());
+    }
+    if let Some(terms_of_service) = &info.terms_of_service {
+        oas.info.terms_of_service = Some(terms_of_service.clone());
+    }
+    if let Some(contact) = &info.contact {
+        oas.info.contact = Some(contact.clone());
+    }
+    if let Some(license) = &info.license {
+        oas.info.license = Some(license.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn document(value: JsonValue) -> OpenAPI {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn operation(tags: &[&str]) -> JsonValue {
+        json!({ "tags": tags, "responses": { "200": { "description": "ok" } } })
+    }
+
+    fn filtered(oas: &OpenAPI, filter: JsonValue) -> Vec<String> {
+        let mut oas = oas.clone();
+        filter_operations(&mut oas, &serde_json::from_value(filter).unwrap());
+        oas.operations()
+            .map(|(path, method, _)| format!("{} {}", method, path))
+            .collect()
+    }
+
+    #[test]
+    fn is_under_matches_whole_segments() {
+        assert!(is_under("/admin", "/admin"));
+        assert!(is_under("/admin/users", "/admin"));
+        assert!(is_under("/admin/users", "/admin/"));
+        assert!(is_under("/admin", "/"));
+        assert!(!is_under("/administrator", "/admin"));
+        assert!(!is_under("/users/admin", "/admin"));
+    }
+
+    #[test]
+    fn filter_operations_by_tag_path_and_extension() {
+        let oas = document(json!({
+            "openapi": "3.0.3",
+            "info": { "title": "Shop", "version": "1" },
+            "tags": [{ "name": "Admin" }, { "name": "Orders" }, { "name": "Unused" }],
+            "x-tagGroups": [
+                { "name": "Back office", "tags": ["Admin"] },
+                { "name": "Shop", "tags": ["Orders"] }
+            ],
+            "paths": {
+                "/admin/users": {
+                    "get": operation(&["Admin"]),
+                    "delete": { "x-internal": true, "tags": ["Admin"], "responses": {} }
+                },
+                "/administrator": { "get": operation(&["Orders"]) },
+                "/orders": {
+                    "x-internal": false,
+                    "get": operation(&["Orders"]),
+                    "post": operation(&["Orders", "Admin"])
+                }
+            }
+        }));
+
+        assert_eq!(
+            filtered(&oas, json!({ "includeTags": ["Admin"] })),
+            ["get /admin/users", "delete /admin/users", "post /orders"]
+        );
+        assert_eq!(
+            filtered(&oas, json!({ "excludeTags": ["Admin"] })),
+            ["get /administrator", "get /orders"]
+        );
+        assert_eq!(
+            filtered(&oas, json!({ "includePaths": ["/admin"] })),
+            ["get /admin/users", "delete /admin/users"]
+        );
+        assert_eq!(
+            filtered(&oas, json!({ "excludePaths": ["/admin", "/orders"] })),
+            ["get /administrator"]
+        );
+        assert_eq!(
+            filtered(&oas, json!({ "includeExtensions": ["x-internal"] })),
+            ["delete /admin/users"]
+        );
+        assert_eq!(
+            filtered(
+                &oas,
+                json!({ "includeTags": ["Admin"], "excludeExtensions": ["x-internal"] })
+            ),
+            ["get /admin/users", "post /orders"]
+        );
+
+        // Tags whose operations were all filtered out are dropped, unused tags are kept
+        let mut admin_only = oas.clone();
+        let filter =
+            serde_json::from_value(json!({ "excludePaths": ["/administrator", "/orders"] }));
+        filter_operations(&mut admin_only, &filter.unwrap());
+        let tags: Vec<&str> = admin_only
+            .tags
+            .iter()
+            .map(|tag| tag.name.as_str())
+            .collect();
+        assert_eq!(tags, ["Admin", "Unused"]);
+        assert_eq!(
+            admin_only.extensions["x-tagGroups"],
+            json!([{ "name": "Back office", "tags": ["Admin"] }])
+        );
+        assert!(!admin_only.paths.paths.contains_key("/orders"));
+    }
+
+    #[test]
+    fn prune_components_keeps_referenced_components() {
+        let oas = document(json!({
+            "openapi": "3.0.3",
+            "info": { "title": "Pets", "version": "1" },
+            "security": [{ "ApiKey": [] }],
+            "paths": {
+                "/pets": {
+                    "get": {
+                        "security": [{ "OAuth": ["read"] }],
+                        "responses": {
+                            "200": {
+                                "description": "ok",
+                                "content": {
+                                    "application/json": {
+                                        "schema": { "$ref": "#/components/schemas/Pet" }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            },
+            "components": {
+                "schemas": {
+                    "Pet": {
+                        "oneOf": [
+                            { "$ref": "#/components/schemas/Cat" },
+                            { "$ref": "#/components/schemas/Dog" }
+                        ],
+                        "discriminator": {
+                            "propertyName": "kind",
+                            "mapping": {
+                                "cat": "#/components/schemas/Cat",
+                                "dog": "Dog",
+                                "bird": "Bird"
+                            }
+                        }
+                    },
+                    "Cat": { "type": "object" },
+                    "Dog": { "$ref": "#/components/schemas/Animal" },
+                    "Animal": { "type": "object" },
+                    "Bird": { "type": "object" },
+                    "Unused": { "type": "object" }
+                },
+                "responses": {
+                    "NotFound": { "description": "not found" }
+                },
+                "securitySchemes": {
+                    "ApiKey": { "type": "apiKey", "in": "header", "name": "X-Api-Key" },
+                    "OAuth": {
+                        "type": "oauth2",
+                        "flows": {
+                            "implicit": {
+                                "authorizationUrl": "https://example.com/authorize",
+                                "scopes": { "read": "Read pets" }
+                            }
+                        }
+                    },
+                    "Basic": { "type": "http", "scheme": "basic" }
+                }
+            }
+        }));
+
+        let pruned = prune_components(&oas).unwrap();
+        let components = pruned.components.unwrap();
+        let schemas: Vec<&str> = components.schemas.keys().map(String::as_str).collect();
+        assert_eq!(schemas, ["Pet", "Cat", "Dog", "Animal", "Bird"]);
+        assert!(components.responses.is_empty());
+        let schemes: Vec<&str> = components
+            .security_schemes
+            .keys()
+            .map(String::as_str)
+            .collect();
+        assert_eq!(schemes, ["ApiKey", "OAuth"]);
+    }
+}
@@ -1,130 +1,102 @@
 //! Paths and components merging logic
 
-use crate::data::{ErrorMergeResult, ErrorType, MergeInput, PathModification};
+use crate::data::{MergeInput, MergeOptions, PathModification};
+use crate::diagnostics::{
+    Diagnostic, COMPONENT_RENAMED, OPERATION_ID_RENAMED, SECURITY_SCHEMES_DISCARDED,
+};
+use crate::error::{ErrorLocation, MergeError};
 use crate::merge::component_equivalence::components_equal;
 use crate::merge::dispute::{apply_dispute, get_dispute, DisputeStatus};
+use crate::merge::extensions::ExtensionMerger;
+use crate::merge::hoisting::{hoist_inline_schemas, HoistedSchemas};
+use crate::merge::json_pointer::JsonPointer;
 use crate::merge::operation_selection::run_operation_selection;
+use crate::merge::origins::{Origin, Origins};
 use crate::merge::reference_walker::walk_all_references;
+use crate::merge::tags::{apply_tag_modification, tag_group_name, tag_untagged_operations};
 use indexmap::IndexMap;
 use openapiv3::*;
 
 /// Result of merging paths and components
-pub type PathAndComponents = (Paths, Components);
+pub type PathAndComponents = (Paths, Components, Origins);
+
+/// The new location of every path item and component that an input renamed
+pub type ReferenceModification = std::collections::HashMap<JsonPointer, JsonPointer>;
 
 /// Merge paths and components from all inputs
 pub fn merge_paths_and_components(
     inputs: &MergeInput,
-) -> Result<PathAndComponents, ErrorMergeResult> {
+    options: &MergeOptions,
+    extension_merger: &mut ExtensionMerger,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<PathAndComponents, MergeError> {
     let mut seen_operation_ids = std::collections::HashSet::new();
     let mut result_paths = Paths::default();
     let mut result_components = Components::default();
+    let mut origins = Origins::new();
+    let mut security_schemes_input = None;
 
     for (input_index, input) in inputs.iter().enumerate() {
         let dispute = get_dispute(input);
 
         // Apply operation selection - clone the OAS first
-        let oas_json = serde_json::to_value(&input.oas).map_err(|e| ErrorMergeResult {
-            error_type: ErrorType::NoInputs,
-            message: format!("Failed to serialize OAS: {}", e),
-        })?;
-        let mut oas: OpenAPI = serde_json::from_value(oas_json).map_err(|e| ErrorMergeResult {
-            error_type: ErrorType::NoInputs,
-            message: format!("Failed to deserialize OAS: {}", e),
-        })?;
+        let serialization_error = |e: serde_json::Error| MergeError::Serialization {
+            message: e.to_string(),
+            location: ErrorLocation::for_input(input_index, input).into(),
+        };
+        let oas_json = serde_json::to_value(&input.oas).map_err(serialization_error)?;
+        let mut oas: OpenAPI = serde_json::from_value(oas_json).map_err(serialization_error)?;
         oas = run_operation_selection(oas, input.operation_selection.as_ref());
 
         // Drop path items with no operations
         oas = drop_path_items_with_no_operations(oas);
 
-        // Reference modification map
-        let mut reference_modification: std::collections::HashMap<String, String> =
-            std::collections::HashMap::new();
-
-        // Process components first to build reference modification map
-        if let Some(components) = &oas.components {
-            // Process schemas
-            if !components.schemas.is_empty() {
-                process_schemas(
-                    &mut result_components.schemas,
-                    &components.schemas,
-                    &dispute,
-                    &mut reference_modification,
-                )?;
-            }
-
-            // Process responses
-            if !components.responses.is_empty() {
-                process_responses(
-                    &mut result_components.responses,
-                    &components.responses,
-                    &dispute,
-                    &mut reference_modification,
-                )?;
-            }
-
-            // Process parameters
-            if !components.parameters.is_empty() {
-                process_parameters(
-                    &mut result_components.parameters,
-                    &components.parameters,
-                    &dispute,
-                    &mut reference_modification,
-                )?;
-            }
-
-            // Process examples
-            if !components.examples.is_empty() {
-                process_components_with_prefix(
-                    &mut result_components.examples,
-                    &components.examples,
-                    &dispute,
-                    &mut reference_modification,
-                    "examples",
-                )?;
-            }
+        apply_tag_modification(&mut oas, input.tag_modification.as_ref());
 
-            // Process request bodies
-            if !components.request_bodies.is_empty() {
-                process_components_with_prefix(
-                    &mut result_components.request_bodies,
-                    &components.request_bodies,
-                    &dispute,
-                    &mut reference_modification,
-                    "requestBodies",
-                )?;
-            }
+        // Operations without tags would not be part of any generated tag group
+        if options.tag_groups {
+            tag_untagged_operations(&mut oas, &tag_group_name(input));
+        }
 
-            // Process headers
-            if !components.headers.is_empty() {
-                process_components_with_prefix(
-                    &mut result_components.headers,
-                    &components.headers,
-                    &dispute,
-                    &mut reference_modification,
-                    "headers",
-                )?;
-            }
+        // Inline schemas become components, so that they are deduplicated and disputed like
+        // any other schema
+        let hoisted = match &options.hoist_inline_schemas {
+            Some(config) => hoist_inline_schemas(&mut oas, config),
+            None => HoistedSchemas::new(),
+        };
+        let input_pointer = |prefix: &str, key: &str| match hoisted.get(key) {
+            Some(pointer) if prefix == "schemas" => pointer.clone(),
+            _ => JsonPointer::component(prefix, key),
+        };
 
-            // Process links
-            if !components.links.is_empty() {
-                process_components_with_prefix(
-                    &mut result_components.links,
-                    &components.links,
-                    &dispute,
-                    &mut reference_modification,
-                    "links",
-                )?;
-            }
+        // Reference modification map
+        let mut reference_modification = ReferenceModification::new();
 
-            // Process callbacks
-            if !components.callbacks.is_empty() {
-                process_components_with_prefix(
-                    &mut result_components.callbacks,
-                    &components.callbacks,
+        // Process components first to build reference modification map. The components are
+        // copied again once their references have been updated.
+        let mut placements: Vec<(&str, Placements)> = Vec::new();
+        if let Some(components) = &oas.components {
+            // Whether a component is equal to one that is already in the result depends on how
+            // the components that it references are renamed, and those may be placed after it.
+            // Place the components again with the renames of the previous attempt until the
+            // renames stop changing.
+            let result_before = result_components.clone();
+            let mut assumed = ReferenceModification::new();
+            for _ in 0..MAX_PLACEMENT_ATTEMPTS {
+                result_components = result_before.clone();
+                reference_modification = ReferenceModification::new();
+                placements = place_components(
+                    &mut result_components,
+                    components,
                     &dispute,
                     &mut reference_modification,
-                    "callbacks",
-                )?;
+                    &assumed,
+                )
+                .map_err(|e| e.for_input(input_index, input))?;
+                if reference_modification == assumed {
+                    break;
+                }
+                assumed = reference_modification.clone();
             }
 
             // Security schemes - just take from first file that has any
@@ -132,69 +104,395 @@ pub fn merge_paths_and_components(
                 && !components.security_schemes.is_empty()
             {
                 result_components.security_schemes = components.security_schemes.clone();
+                security_schemes_input = Some(input_index);
+                for key in components.security_schemes.keys() {
+                    let pointer = JsonPointer::component("securitySchemes", key);
+                    origins.insert(
+                        pointer.clone(),
+                        Origin {
+                            input_index,
+                            original_pointer: pointer,
+                        },
+                    );
+                }
+            } else if !components.security_schemes.is_empty()
+                && components.security_schemes != result_components.security_schemes
+            {
+                let security_schemes =
+                    JsonPointer::from_segments(["components", "securitySchemes"]);
+                diagnostics.push(Diagnostic::warning(
+                    SECURITY_SCHEMES_DISCARDED,
+                    format!(
+                        "The security schemes of this input were discarded in favour of those of input {}",
+                        security_schemes_input.unwrap_or_default()
+                    ),
+                    ErrorLocation::for_input(input_index, input)
+                        .with_input_pointer(security_schemes.clone())
+                        .with_output_pointer(security_schemes),
+                ));
             }
+
+            extension_merger.merge(
+                &mut result_components.extensions,
+                &components.extensions,
+                &JsonPointer::from_segments(["components"]),
+                input_index,
+            );
         }
 
+        extension_merger.merge(
+            &mut result_paths.extensions,
+            &oas.paths.extensions,
+            &JsonPointer::from_segments(["paths"]),
+            input_index,
+        );
+
         // Process paths
         let path_modification = input.path_modification.as_ref();
-        for (original_path, path_item) in oas.paths.iter() {
+        let mut new_paths: Vec<(String, String)> = Vec::new();
+        for original_path in oas.paths.paths.keys() {
             let new_path = apply_path_modification(original_path, path_modification);
 
             if original_path != &new_path {
                 reference_modification.insert(
-                    format!("#/paths/{}", original_path),
-                    format!("#/paths/{}", new_path),
+                    JsonPointer::path(original_path),
+                    JsonPointer::path(&new_path),
                 );
             }
 
             // Check for duplicate paths
-            if result_paths.paths.contains_key(&new_path) {
-                return Err(ErrorMergeResult {
-                    error_type: ErrorType::DuplicatePaths,
-                    message: format!(
-                        "Input {}: The path '{}' maps to '{}' and this has already been added by another input file",
-                        input_index, original_path, new_path
-                    ),
+            if result_paths.paths.contains_key(&new_path)
+                || new_paths.iter().any(|(_, path)| path == &new_path)
+            {
+                return Err(MergeError::DuplicatePath {
+                    original_path: original_path.clone(),
+                    new_path: new_path.clone(),
+                    location: ErrorLocation::for_input(input_index, input)
+                        .with_input_pointer(JsonPointer::path(original_path))
+                        .with_output_pointer(JsonPointer::path(&new_path))
+                        .into(),
                 });
             }
 
+            new_paths.push((original_path.clone(), new_path));
+        }
+
+        // Update references in the OAS after processing both components and paths
+        walk_all_references(&mut oas, |reference| {
+            rewrite_reference(reference, &reference_modification)
+        })
+        .map_err(|e| e.for_input(input_index, input))?;
+
+        // Copy the updated components and path items into the result
+        if let Some(components) = &oas.components {
+            for (prefix, placed) in &placements {
+                match *prefix {
+                    "schemas" => copy_placed_components(
+                        &mut result_components.schemas,
+                        &components.schemas,
+                        placed,
+                    ),
+                    "responses" => copy_placed_components(
+                        &mut result_components.responses,
+                        &components.responses,
+                        placed,
+                    ),
+                    "parameters" => copy_placed_components(
+                        &mut result_components.parameters,
+                        &components.parameters,
+                        placed,
+                    ),
+                    "examples" => copy_placed_components(
+                        &mut result_components.examples,
+                        &components.examples,
+                        placed,
+                    ),
+                    "requestBodies" => copy_placed_components(
+                        &mut result_components.request_bodies,
+                        &components.request_bodies,
+                        placed,
+                    ),
+                    "headers" => copy_placed_components(
+                        &mut result_components.headers,
+                        &components.headers,
+                        placed,
+                    ),
+                    "links" => copy_placed_components(
+                        &mut result_components.links,
+                        &components.links,
+                        placed,
+                    ),
+                    "callbacks" => copy_placed_components(
+                        &mut result_components.callbacks,
+                        &components.callbacks,
+                        placed,
+                    ),
+                    _ => {}
+                }
+
+                for (key, placed_key) in placed {
+                    if is_numeric_rename(key, placed_key, dispute.as_ref()) {
+                        diagnostics.push(Diagnostic::warning(
+                            COMPONENT_RENAMED,
+                            format!(
+                                "The {} definition \"{}\" conflicts with a different definition in a previous input and was renamed to \"{}\"",
+                                prefix, key, placed_key
+                            ),
+                            ErrorLocation::for_input(input_index, input)
+                                .with_input_pointer(input_pointer(prefix, key))
+                                .with_output_pointer(JsonPointer::component(prefix, placed_key)),
+                        ));
+                    }
+                    origins.insert(
+                        JsonPointer::component(prefix, placed_key),
+                        Origin {
+                            input_index,
+                            original_pointer: input_pointer(prefix, key),
+                        },
+                    );
+                }
+            }
+        }
+
+        for (original_path, new_path) in new_paths {
             // Clone path item and ensure unique operation IDs
-            let mut copy_path_item = path_item.clone();
-            ensure_unique_operation_ids(
+            let mut copy_path_item = oas.paths.paths[&original_path].clone();
+            let renamed_operation_ids = ensure_unique_operation_ids(
                 &mut copy_path_item,
                 &mut seen_operation_ids,
                 dispute.as_ref(),
-            )?;
+            )
+            .map_err(|e| {
+                e.for_input(input_index, input).at(
+                    JsonPointer::path(&original_path),
+                    JsonPointer::path(&new_path),
+                )
+            })?;
+            for (operation_id, unique_id) in renamed_operation_ids {
+                if is_numeric_rename(&operation_id, &unique_id, dispute.as_ref()) {
+                    diagnostics.push(Diagnostic::warning(
+                        OPERATION_ID_RENAMED,
+                        format!(
+                            "The operationId '{}' is already used by a previous input and was renamed to '{}'",
+                            operation_id, unique_id
+                        ),
+                        ErrorLocation::for_input(input_index, input)
+                            .with_input_pointer(JsonPointer::path(&original_path))
+                            .with_output_pointer(JsonPointer::path(&new_path)),
+                    ));
+                }
+            }
 
+            origins.insert(
+                JsonPointer::path(&new_path),
+                Origin {
+                    input_index,
+                    original_pointer: JsonPointer::path(&original_path),
+                },
+            );
             result_paths.paths.insert(new_path, copy_path_item);
         }
+    }
 
-        // Update references in the OAS after processing both components and paths
-        walk_all_references(&mut oas, |ref_path| {
-            if let Some(new_ref) = reference_modification.get(ref_path) {
-                return new_ref.clone();
-            }
+    Ok((result_paths, result_components, origins))
+}
 
-            // Check for prefix matches
-            let matching_keys: Vec<_> = reference_modification
-                .keys()
-                .filter(|key| key.starts_with(&format!("{}/", ref_path)))
-                .collect();
+/// How many times the components of an input are placed before the renames are accepted
+const MAX_PLACEMENT_ATTEMPTS: usize = 8;
 
-            if matching_keys.len() > 1 {
-                panic!(
-                    "Found more than one matching key for reference '{}': {:?}",
-                    ref_path, matching_keys
-                );
-            } else if matching_keys.len() == 1 {
-                return reference_modification[matching_keys[0]].clone();
-            }
+/// Place every kind of component of an input into the result, except for the security schemes.
+/// References in the components are rewritten with the renames made so far, or else with the
+/// `assumed` renames, before they are compared with the components already in the result.
+fn place_components(
+    result: &mut Components,
+    components: &Components,
+    dispute: &Option<crate::data::Dispute>,
+    reference_modification: &mut ReferenceModification,
+    assumed: &ReferenceModification,
+) -> Result<Vec<(&'static str, Placements)>, MergeError> {
+    let mut placements = Vec::new();
+    // Process schemas
+    if !components.schemas.is_empty() {
+        placements.push((
+            "schemas",
+            process_schemas(
+                &mut result.schemas,
+                &components.schemas,
+                dispute,
+                reference_modification,
+                assumed,
+            )?,
+        ));
+    }
+
+    // Process responses
+    if !components.responses.is_empty() {
+        placements.push((
+            "responses",
+            process_responses(
+                &mut result.responses,
+                &components.responses,
+                dispute,
+                reference_modification,
+                assumed,
+            )?,
+        ));
+    }
+
+    // Process parameters
+    if !components.parameters.is_empty() {
+        placements.push((
+            "parameters",
+            process_parameters(
+                &mut result.parameters,
+                &components.parameters,
+                dispute,
+                reference_modification,
+                assumed,
+            )?,
+        ));
+    }
+
+    // Process examples
+    if !components.examples.is_empty() {
+        placements.push((
+            "examples",
+            process_components_with_prefix(
+                &mut result.examples,
+                &components.examples,
+                dispute,
+                reference_modification,
+                assumed,
+                "examples",
+            )?,
+        ));
+    }
+
+    // Process request bodies
+    if !components.request_bodies.is_empty() {
+        placements.push((
+            "requestBodies",
+            process_components_with_prefix(
+                &mut result.request_bodies,
+                &components.request_bodies,
+                dispute,
+                reference_modification,
+                assumed,
+                "requestBodies",
+            )?,
+        ));
+    }
+
+    // Process headers
+    if !components.headers.is_empty() {
+        placements.push((
+            "headers",
+            process_components_with_prefix(
+                &mut result.headers,
+                &components.headers,
+                dispute,
+                reference_modification,
+                assumed,
+                "headers",
+            )?,
+        ));
+    }
+
+    // Process links
+    if !components.links.is_empty() {
+        placements.push((
+            "links",
+            process_components_with_prefix(
+                &mut result.links,
+                &components.links,
+                dispute,
+                reference_modification,
+                assumed,
+                "links",
+            )?,
+        ));
+    }
+
+    // Process callbacks
+    if !components.callbacks.is_empty() {
+        placements.push((
+            "callbacks",
+            process_components_with_prefix(
+                &mut result.callbacks,
+                &components.callbacks,
+                dispute,
+                reference_modification,
+                assumed,
+                "callbacks",
+            )?,
+        ));
+    }
+
+    Ok(placements)
+}
+
+/// Whether a name was changed by adding a number to avoid a conflict, rather than by the
+/// dispute configuration of its input
+fn is_numeric_rename(
+    original: &str,
+    renamed: &str,
+    dispute: Option<&crate::data::Dispute>,
+) -> bool {
+    renamed != original
+        && renamed != apply_dispute(dispute, original, DisputeStatus::Undisputed)
+        && renamed != apply_dispute(dispute, original, DisputeStatus::Disputed)
+}
+
+/// Replace the components that this input placed into the result with their updated versions
+fn copy_placed_components<T: Clone>(
+    results: &mut IndexMap<String, T>,
+    components: &IndexMap<String, T>,
+    placed: &Placements,
+) {
+    for (key, placed_key) in placed {
+        if let Some(component) = components.get(key) {
+            results.insert(placed_key.clone(), component.clone());
+        }
+    }
+}
+
+/// Rewrite a single reference using the map of renamed elements. A reference that points at, or
+/// inside, a renamed element is moved along with it; a reference inside more than one renamed
+/// element is an error. References to other documents, and references that do not need to
+/// change, are returned as they were written.
+fn rewrite_reference(
+    reference: &str,
+    reference_modification: &ReferenceModification,
+) -> Result<String, MergeError> {
+    let pointer = match JsonPointer::parse(reference) {
+        Some(pointer) => pointer,
+        None => return Ok(reference.to_string()),
+    };
 
-            ref_path.to_string()
-        });
+    if let Some(new_pointer) = reference_modification.get(&pointer) {
+        return Ok(new_pointer.to_string());
     }
 
-    Ok((result_paths, result_components))
+    let mut candidates: Vec<&JsonPointer> = reference_modification
+        .keys()
+        .filter(|key| pointer.starts_with(key))
+        .collect();
+
+    match candidates.as_slice() {
+        [] => Ok(reference.to_string()),
+        [key] => Ok(pointer
+            .rebase(key, &reference_modification[*key])
+            .map_or_else(|| reference.to_string(), |rebased| rebased.to_string())),
+        _ => {
+            candidates.sort();
+            let candidates: Vec<String> = candidates.iter().map(|key| key.to_string()).collect();
+            Err(MergeError::AmbiguousReference {
+                reference: reference.to_string(),
+                candidates,
+                location: ErrorLocation::default().into(),
+            })
+        }
+    }
 }
 
 fn apply_path_modification(path: &str, path_modification: Option<&PathModification>) -> String {
@@ -239,91 +537,48 @@ fn drop_path_items_with_no_operations(mut oas: OpenAPI) -> OpenAPI {
     oas
 }
 
+/// Give every operation in the path item an operationId that has not been seen before.
+/// Returns the operationIds that had to be changed, with their new values.
 fn ensure_unique_operation_ids(
     path_item: &mut ReferenceOr<PathItem>,
     seen_operation_ids: &mut std::collections::HashSet<String>,
     dispute: Option<&crate::data::Dispute>,
-) -> Result<(), ErrorMergeResult> {
-    match path_item {
-        ReferenceOr::Item(item) => {
-            if let Some(op) = item.get.as_mut() {
-                if let Some(operation_id) = &op.operation_id {
-                    let unique_id =
-                        find_unique_operation_id(operation_id, seen_operation_ids, dispute)?;
-                    op.operation_id = Some(unique_id.clone());
-                    seen_operation_ids.insert(unique_id);
-                }
-            }
-            if let Some(op) = item.put.as_mut() {
-                if let Some(operation_id) = &op.operation_id {
-                    let unique_id =
-                        find_unique_operation_id(operation_id, seen_operation_ids, dispute)?;
-                    op.operation_id = Some(unique_id.clone());
-                    seen_operation_ids.insert(unique_id);
-                }
-            }
-            if let Some(op) = item.post.as_mut() {
-                if let Some(operation_id) = &op.operation_id {
-                    let unique_id =
-                        find_unique_operation_id(operation_id, seen_operation_ids, dispute)?;
-                    op.operation_id = Some(unique_id.clone());
-                    seen_operation_ids.insert(unique_id);
-                }
-            }
-            if let Some(op) = item.delete.as_mut() {
-                if let Some(operation_id) = &op.operation_id {
-                    let unique_id =
-                        find_unique_operation_id(operation_id, seen_operation_ids, dispute)?;
-                    op.operation_id = Some(unique_id.clone());
-                    seen_operation_ids.insert(unique_id);
-                }
-            }
-            if let Some(op) = item.patch.as_mut() {
-                if let Some(operation_id) = &op.operation_id {
-                    let unique_id =
-                        find_unique_operation_id(operation_id, seen_operation_ids, dispute)?;
-                    op.operation_id = Some(unique_id.clone());
-                    seen_operation_ids.insert(unique_id);
-                }
-            }
-            if let Some(op) = item.head.as_mut() {
-                if let Some(operation_id) = &op.operation_id {
-                    let unique_id =
-                        find_unique_operation_id(operation_id, seen_operation_ids, dispute)?;
-                    op.operation_id = Some(unique_id.clone());
-                    seen_operation_ids.insert(unique_id);
-                }
-            }
-            if let Some(op) = item.trace.as_mut() {
-                if let Some(operation_id) = &op.operation_id {
-                    let unique_id =
-                        find_unique_operation_id(operation_id, seen_operation_ids, dispute)?;
-                    op.operation_id = Some(unique_id.clone());
-                    seen_operation_ids.insert(unique_id);
+) -> Result<Vec<(String, String)>, MergeError> {
+    let mut renamed = Vec::new();
+
+    // References don't have operation IDs
+    if let ReferenceOr::Item(item) = path_item {
+        let operations = [
+            &mut item.get,
+            &mut item.put,
+            &mut item.post,
+            &mut item.delete,
+            &mut item.patch,
+            &mut item.head,
+            &mut item.trace,
+            &mut item.options,
+        ];
+        for op in operations.into_iter().flatten() {
+            if let Some(operation_id) = &op.operation_id {
+                let unique_id =
+                    find_unique_operation_id(operation_id, seen_operation_ids, dispute)?;
+                if &unique_id != operation_id {
+                    renamed.push((operation_id.clone(), unique_id.clone()));
                 }
+                op.operation_id = Some(unique_id.clone());
+                seen_operation_ids.insert(unique_id);
             }
-            if let Some(op) = item.options.as_mut() {
-                if let Some(operation_id) = &op.operation_id {
-                    let unique_id =
-                        find_unique_operation_id(operation_id, seen_operation_ids, dispute)?;
-                    op.operation_id = Some(unique_id.clone());
-                    seen_operation_ids.insert(unique_id);
-                }
-            }
-        }
-        ReferenceOr::Reference { .. } => {
-            // References don't have operation IDs
         }
     }
 
-    Ok(())
+    Ok(renamed)
 }
 
 fn find_unique_operation_id(
     operation_id: &str,
     seen_operation_ids: &std::collections::HashSet<String>,
     dispute: Option<&crate::data::Dispute>,
-) -> Result<String, ErrorMergeResult> {
+) -> Result<String, MergeError> {
     if !seen_operation_ids.contains(operation_id) {
         return Ok(operation_id.to_string());
     }
@@ -344,36 +599,47 @@ fn find_unique_operation_id(
         }
     }
 
-    Err(ErrorMergeResult {
-        error_type: ErrorType::OperationIdConflict,
-        message: format!(
-            "Could not resolve a conflict for the operationId '{}'",
-            operation_id
-        ),
+    Err(MergeError::OperationIdConflict {
+        operation_id: operation_id.to_string(),
+        location: ErrorLocation::default().into(),
     })
 }
 
+/// The components that an input placed into a previously empty slot of the result, as
+/// pairs of their original key and the key they were placed under
+pub type Placements = Vec<(String, String)>;
+
 // Helper functions for processing different component types
 pub fn process_schemas(
     results: &mut IndexMap<String, ReferenceOr<Schema>>,
     schemas: &IndexMap<String, ReferenceOr<Schema>>,
     dispute: &Option<crate::data::Dispute>,
-    reference_modification: &mut std::collections::HashMap<String, String>,
-) -> Result<(), ErrorMergeResult> {
-    process_components_with_prefix(results, schemas, dispute, reference_modification, "schemas")
+    reference_modification: &mut ReferenceModification,
+    assumed: &ReferenceModification,
+) -> Result<Placements, MergeError> {
+    process_components_with_prefix(
+        results,
+        schemas,
+        dispute,
+        reference_modification,
+        assumed,
+        "schemas",
+    )
 }
 
 pub fn process_responses(
     results: &mut IndexMap<String, ReferenceOr<Response>>,
     responses: &IndexMap<String, ReferenceOr<Response>>,
     dispute: &Option<crate::data::Dispute>,
-    reference_modification: &mut std::collections::HashMap<String, String>,
-) -> Result<(), ErrorMergeResult> {
+    reference_modification: &mut ReferenceModification,
+    assumed: &ReferenceModification,
+) -> Result<Placements, MergeError> {
     process_components_with_prefix(
         results,
         responses,
         dispute,
         reference_modification,
+        assumed,
         "responses",
     )
 }
@@ -382,13 +648,15 @@ pub fn process_parameters(
     results: &mut IndexMap<String, ReferenceOr<Parameter>>,
     parameters: &IndexMap<String, ReferenceOr<Parameter>>,
     dispute: &Option<crate::data::Dispute>,
-    reference_modification: &mut std::collections::HashMap<String, String>,
-) -> Result<(), ErrorMergeResult> {
+    reference_modification: &mut ReferenceModification,
+    assumed: &ReferenceModification,
+) -> Result<Placements, MergeError> {
     process_components_with_prefix(
         results,
         parameters,
         dispute,
         reference_modification,
+        assumed,
         "parameters",
     )
 }
@@ -397,39 +665,52 @@ fn process_components_with_prefix<T>(
     results: &mut IndexMap<String, T>,
     components: &IndexMap<String, T>,
     dispute: &Option<crate::data::Dispute>,
-    reference_modification: &mut std::collections::HashMap<String, String>,
+    reference_modification: &mut ReferenceModification,
+    assumed: &ReferenceModification,
     prefix: &str,
-) -> Result<(), ErrorMergeResult>
+) -> Result<Placements, MergeError>
 where
-    T: Clone + serde::Serialize,
+    T: Clone + serde::Serialize + serde::de::DeserializeOwned,
 {
+    let mut placements = Placements::new();
+
     for (key, component) in components {
         let modified_key = apply_dispute(dispute.as_ref(), key, DisputeStatus::Undisputed);
 
         if modified_key != *key {
             reference_modification.insert(
-                format!("#/components/{}/{}", prefix, key),
-                format!("#/components/{}/{}", prefix, modified_key),
+                JsonPointer::component(prefix, key),
+                JsonPointer::component(prefix, &modified_key),
             );
         }
 
-        if results.get(&modified_key).is_none()
-            || components_equal::<T>(&results[&modified_key], component)
-        {
+        // The components in the result already have their references rewritten
+        let rewritten = rewritten_component(component, reference_modification, assumed);
+        let equals = |existing: &T| {
+            rewritten
+                .as_ref()
+                .is_some_and(|rewritten| components_equal(existing, rewritten))
+        };
+
+        if results.get(&modified_key).is_none() {
             results.insert(modified_key.clone(), component.clone());
+            placements.push((key.clone(), modified_key));
+        } else if equals(&results[&modified_key]) {
+            // Deduplicated with the component from a previous input
         } else {
             // Conflict resolution logic (same as before)
             let mut schema_placed = false;
 
             if let Some(dispute) = dispute {
                 let preferred_key = apply_dispute(Some(dispute), key, DisputeStatus::Disputed);
-                if results.get(&preferred_key).is_none()
-                    || components_equal(&results[&preferred_key], component)
-                {
-                    results.insert(preferred_key.clone(), component.clone());
+                if results.get(&preferred_key).is_none() || equals(&results[&preferred_key]) {
+                    if results.get(&preferred_key).is_none() {
+                        placements.push((key.clone(), preferred_key.clone()));
+                        results.insert(preferred_key.clone(), component.clone());
+                    }
                     reference_modification.insert(
-                        format!("#/components/{}/{}", prefix, key),
-                        format!("#/components/{}/{}", prefix, preferred_key),
+                        JsonPointer::component(prefix, key),
+                        JsonPointer::component(prefix, &preferred_key),
                     );
                     schema_placed = true;
                 }
@@ -440,9 +721,10 @@ where
                     let try_key = format!("{}{}", key, anti_conflict);
                     if results.get(&try_key).is_none() {
                         results.insert(try_key.clone(), component.clone());
+                        placements.push((key.clone(), try_key.clone()));
                         reference_modification.insert(
-                            format!("#/components/{}/{}", prefix, key),
-                            format!("#/components/{}/{}", prefix, try_key),
+                            JsonPointer::component(prefix, key),
+                            JsonPointer::component(prefix, &try_key),
                         );
                         schema_placed = true;
                         break;
@@ -451,16 +733,152 @@ where
             }
 
             if !schema_placed {
-                return Err(ErrorMergeResult {
-                    error_type: ErrorType::ComponentDefinitionConflict,
-                    message: format!(
-                        "The \"{}\" definition had a duplicate in a previous input and could not be deduplicated.",
-                        key
-                    ),
+                return Err(MergeError::ComponentConflict {
+                    kind: prefix.to_string(),
+                    key: key.clone(),
+                    location: ErrorLocation::default()
+                        .with_input_pointer(JsonPointer::component(prefix, key))
+                        .into(),
                 });
             }
         }
     }
 
+    Ok(placements)
+}
+
+/// The component with its references rewritten as they will be in the result: with the renames
+/// made so far for this input, and otherwise with the assumed renames. Returns `None` if the
+/// component cannot be copied or a reference is ambiguous.
+fn rewritten_component<T>(
+    component: &T,
+    reference_modification: &ReferenceModification,
+    assumed: &ReferenceModification,
+) -> Option<T>
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    let mut renames = assumed.clone();
+    renames.extend(
+        reference_modification
+            .iter()
+            .map(|(from, to)| (from.clone(), to.clone())),
+    );
+    let mut value = serde_json::to_value(component).ok()?;
+    rewrite_json_references(&mut value, &renames).ok()?;
+    serde_json::from_value(value).ok()
+}
+
+fn rewrite_json_references(
+    value: &mut serde_json::Value,
+    reference_modification: &ReferenceModification,
+) -> Result<(), MergeError> {
+    match value {
+        serde_json::Value::Object(fields) => {
+            for (key, child) in fields.iter_mut() {
+                match child {
+                    serde_json::Value::String(reference) if key == "$ref" => {
+                        *reference = rewrite_reference(reference, reference_modification)?;
+                    }
+                    _ => rewrite_json_references(child, reference_modification)?,
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                rewrite_json_references(item, reference_modification)?;
+            }
+        }
+        _ => {}
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn renames(renames: &[(&str, &str)]) -> ReferenceModification {
+        renames
+            .iter()
+            .map(|(from, to)| {
+                (
+                    JsonPointer::parse(from).unwrap(),
+                    JsonPointer::parse(to).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rewrite_reference_moves_references_inside_renamed_elements() {
+        let renames = renames(&[
+            (
+                "#/components/schemas/User",
+                "#/components/schemas/BillingUser",
+            ),
+            ("#/paths/~1users", "#/paths/~1billing~1users"),
+        ]);
+
+        let rewrite = |reference| rewrite_reference(reference, &renames).unwrap();
+        assert_eq!(
+            rewrite("#/components/schemas/User"),
+            "#/components/schemas/BillingUser"
+        );
+        assert_eq!(
+            rewrite("#/components/schemas/User/properties/id"),
+            "#/components/schemas/BillingUser/properties/id"
+        );
+        assert_eq!(
+            rewrite("#/paths/~1users/get"),
+            "#/paths/~1billing~1users/get"
+        );
+        // Segments are matched whole, so a longer name is not mistaken for a renamed one
+        assert_eq!(
+            rewrite("#/components/schemas/Users"),
+            "#/components/schemas/Users"
+        );
+        assert_eq!(
+            rewrite("other.yaml#/components/schemas/User"),
+            "other.yaml#/components/schemas/User"
+        );
+    }
+
+    #[test]
+    fn rewrite_reference_rejects_references_inside_several_renamed_elements() {
+        let renames = renames(&[
+            (
+                "#/components/schemas/User",
+                "#/components/schemas/BillingUser",
+            ),
+            (
+                "#/components/schemas/User/properties/address",
+                "#/components/schemas/Address",
+            ),
+        ]);
+
+        match rewrite_reference(
+            "#/components/schemas/User/properties/address/type",
+            &renames,
+        ) {
+            Err(MergeError::AmbiguousReference {
+                reference,
+                candidates,
+                ..
+            }) => {
+                assert_eq!(
+                    reference,
+                    "#/components/schemas/User/properties/address/type"
+                );
+                assert_eq!(
+                    candidates,
+                    [
+                        "#/components/schemas/User",
+                        "#/components/schemas/User/properties/address"
+                    ]
+                );
+            }
+            other => panic!("expected an ambiguous reference, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Annotating the merged output with the input each element came from
+
+use crate::data::MergeInput;
+use crate::error::{ErrorLocation, MergeError};
+use crate::merge::operation_selection::operation_slots_mut;
+use crate::merge::origins::{Origin, Origins};
+use crate::merge::tags::tag_group_name;
+use openapiv3::{Components, OpenAPI, PathItem, ReferenceOr};
+use serde_json::{json, Value as JsonValue};
+
+/// The extension that records where an element came from
+pub const MERGE_SOURCE_EXTENSION: &str = "x-merge-source";
+
+/// Add an `x-merge-source` extension to every path item, operation and component in the output,
+/// naming the input it came from and its location in that input before any renames or path
+/// modifications. The path items of callbacks are stamped instead of the callbacks themselves,
+/// whose keys are all expressions. Components that are themselves references are left alone, as
+/// are security schemes, which cannot hold extensions in the OpenAPI model this crate uses.
+pub fn annotate_merge_sources(
+    output: &mut OpenAPI,
+    inputs: &MergeInput,
+    origins: &Origins,
+) -> Result<(), MergeError> {
+    if let Some(components) = &mut output.components {
+        stamp_components(components, inputs, origins)?;
+    }
+
+    for (pointer, origin) in origins {
+        match pointer.segments() {
+            [first, path] if first == "paths" => {
+                if let Some(ReferenceOr::Item(item)) = output.paths.paths.get_mut(path) {
+                    stamp_path_item(item, inputs, origin);
+                }
+            }
+            [first, kind, name] if first == "components" && kind == "callbacks" => {
+                let callback = output
+                    .components
+                    .as_mut()
+                    .and_then(|components| components.callbacks.get_mut(name));
+                if let Some(ReferenceOr::Item(callback)) = callback {
+                    for (expression, item) in callback.iter_mut() {
+                        let item_origin = Origin {
+                            input_index: origin.input_index,
+                            original_pointer: origin.original_pointer.child(expression.as_str()),
+                        };
+                        stamp_path_item(item, inputs, &item_origin);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Stamp every component other than the callbacks. The kinds of components keep their
+/// extensions in different places, so they are stamped in their JSON form.
+fn stamp_components(
+    components: &mut Components,
+    inputs: &MergeInput,
+    origins: &Origins,
+) -> Result<(), MergeError> {
+    let serialization_error = |e: serde_json::Error| MergeError::Serialization {
+        message: e.to_string(),
+        location: ErrorLocation::default().into(),
+    };
+    let mut document = serde_json::to_value(&*components).map_err(serialization_error)?;
+    for (pointer, origin) in origins {
+        let (kind, name) = match pointer.segments() {
+            [first, kind, name] if first == "components" && kind != "callbacks" => (kind, name),
+            _ => continue,
+        };
+        let component = document
+            .get_mut(kind)
+            .and_then(|entries| entries.get_mut(name))
+            .and_then(JsonValue::as_object_mut);
+        if let Some(component) = component {
+            if !component.contains_key("$ref") {
+                component.insert(
+                    MERGE_SOURCE_EXTENSION.to_string(),
+                    merge_source(inputs, origin),
+                );
+            }
+        }
+    }
+    *components = serde_json::from_value(document).map_err(serialization_error)?;
+    Ok(())
+}
+
+/// Stamp a path item and each of its operations
+fn stamp_path_item(item: &mut PathItem, inputs: &MergeInput, origin: &Origin) {
+    for (method, operation) in operation_slots_mut(item) {
+        if let Some(operation) = operation {
+            let operation_origin = Origin {
+                input_index: origin.input_index,
+                original_pointer: origin.original_pointer.child(method),
+            };
+            operation.extensions.insert(
+                MERGE_SOURCE_EXTENSION.to_string(),
+                merge_source(inputs, &operation_origin),
+            );
+        }
+    }
+    item.extensions.insert(
+        MERGE_SOURCE_EXTENSION.to_string(),
+        merge_source(inputs, origin),
+    );
+}
+
+fn merge_source(inputs: &MergeInput, origin: &Origin) -> JsonValue {
+    let input = &inputs[origin.input_index];
+    let mut source = json!({
+        "input": tag_group_name(input),
+        "inputIndex": origin.input_index,
+        "originalPointer": origin.original_pointer.to_string(),
+    });
+    if let Some(file) = &input.source {
+        source["file"] = json!(file);
+    }
+    source
+}
@@ -1,244 +1,264 @@
 //! Reference walking and updating logic
 
+use crate::merge::json_pointer::JsonPointer;
 use openapiv3::*;
+use serde_json::Value as JsonValue;
 
-/// Modify function type for reference updates
-pub type Modify = Box<dyn Fn(&str) -> String>;
-
-/// Walk all references in an OpenAPI document and update them
-pub fn walk_all_references<F>(oas: &mut OpenAPI, modify: F)
+/// Walk all references in an OpenAPI document and update them. Stops at the first reference
+/// that cannot be updated and returns its error.
+pub fn walk_all_references<F, E>(oas: &mut OpenAPI, modify: F) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     // Use paths.paths to access the inner IndexMap
     for path_item in oas.paths.paths.values_mut() {
-        walk_path_item_references(path_item, &modify);
+        walk_path_item_references(path_item, &modify)?;
     }
 
     if let Some(components) = &mut oas.components {
-        walk_component_references(components, &modify);
+        walk_component_references(components, &modify)?;
     }
+    Ok(())
 }
 
-fn walk_path_item_references<F>(path_item: &mut ReferenceOr<PathItem>, modify: &F)
+fn walk_path_item_references<F, E>(
+    path_item: &mut ReferenceOr<PathItem>,
+    modify: &F,
+) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     match path_item {
         ReferenceOr::Item(item) => {
             // Operations are Option<Operation>, not ReferenceOr<Operation>
             if let Some(op) = &mut item.get {
-                walk_operation_references(op, modify);
+                walk_operation_references(op, modify)?;
             }
             if let Some(op) = &mut item.put {
-                walk_operation_references(op, modify);
+                walk_operation_references(op, modify)?;
             }
             if let Some(op) = &mut item.post {
-                walk_operation_references(op, modify);
+                walk_operation_references(op, modify)?;
             }
             if let Some(op) = &mut item.delete {
-                walk_operation_references(op, modify);
+                walk_operation_references(op, modify)?;
             }
             if let Some(op) = &mut item.options {
-                walk_operation_references(op, modify);
+                walk_operation_references(op, modify)?;
             }
             if let Some(op) = &mut item.head {
-                walk_operation_references(op, modify);
+                walk_operation_references(op, modify)?;
             }
             if let Some(op) = &mut item.patch {
-                walk_operation_references(op, modify);
+                walk_operation_references(op, modify)?;
             }
             if let Some(op) = &mut item.trace {
-                walk_operation_references(op, modify);
+                walk_operation_references(op, modify)?;
             }
             // Parameters is Vec, use iter_mut
             for param in item.parameters.iter_mut() {
-                walk_parameter_references(param, modify);
+                walk_parameter_references(param, modify)?;
             }
         }
         ReferenceOr::Reference { reference } => {
-            *reference = modify(reference);
+            *reference = modify(reference)?;
         }
     }
+    Ok(())
 }
 
-fn walk_operation_references<F>(operation: &mut Operation, modify: &F)
+fn walk_operation_references<F, E>(operation: &mut Operation, modify: &F) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     // Parameters is Vec
     for param in operation.parameters.iter_mut() {
-        walk_parameter_references(param, modify);
+        walk_parameter_references(param, modify)?;
     }
     if let Some(request_body) = &mut operation.request_body {
-        walk_request_body_references(request_body, modify);
+        walk_request_body_references(request_body, modify)?;
     }
     for response in operation.responses.responses.values_mut() {
-        walk_response_references(response, modify);
+        walk_response_references(response, modify)?;
     }
     // Note: callbacks are not directly on Operation in openapiv3 v1.0.4
     // They would be handled through extensions if needed
+    Ok(())
 }
 
-fn walk_schema_references<F>(schema: &mut ReferenceOr<Schema>, modify: &F)
+fn walk_schema_references<F, E>(schema: &mut ReferenceOr<Schema>, modify: &F) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     match schema {
         ReferenceOr::Reference { reference } => {
-            *reference = modify(reference);
+            *reference = modify(reference)?;
         }
         ReferenceOr::Item(schema_item) => {
-            walk_schema_kind_references(&mut schema_item.schema_kind, modify);
+            walk_schema_kind_references(&mut schema_item.schema_kind, modify)?;
         }
     }
+    Ok(())
 }
 
-fn walk_boxed_schema_references<F>(schema: &mut ReferenceOr<Box<Schema>>, modify: &F)
+fn walk_boxed_schema_references<F, E>(
+    schema: &mut ReferenceOr<Box<Schema>>,
+    modify: &F,
+) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     match schema {
         ReferenceOr::Reference { reference } => {
-            *reference = modify(reference);
+            *reference = modify(reference)?;
         }
         ReferenceOr::Item(schema_item) => {
-            walk_schema_kind_references(&mut schema_item.schema_kind, modify);
+            walk_schema_kind_references(&mut schema_item.schema_kind, modify)?;
         }
     }
+    Ok(())
 }
 
 /// Walk references in a Box<ReferenceOr<Schema>>
-fn walk_box_ref_schema_references<F>(schema: &mut Box<ReferenceOr<Schema>>, modify: &F)
+fn walk_box_ref_schema_references<F, E>(
+    schema: &mut Box<ReferenceOr<Schema>>,
+    modify: &F,
+) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
-    walk_schema_references(schema.as_mut(), modify);
+    walk_schema_references(schema.as_mut(), modify)?;
+    Ok(())
 }
 
-fn walk_schema_kind_references<F>(schema_kind: &mut SchemaKind, modify: &F)
+fn walk_schema_kind_references<F, E>(schema_kind: &mut SchemaKind, modify: &F) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     match schema_kind {
         SchemaKind::Type(typ) => {
-            walk_type_references(typ, modify);
+            walk_type_references(typ, modify)?;
         }
         SchemaKind::OneOf { one_of } => {
             for s in one_of.iter_mut() {
-                walk_schema_references(s, modify);
+                walk_schema_references(s, modify)?;
             }
         }
         SchemaKind::AllOf { all_of } => {
             for s in all_of.iter_mut() {
-                walk_schema_references(s, modify);
+                walk_schema_references(s, modify)?;
             }
         }
         SchemaKind::AnyOf { any_of } => {
             for s in any_of.iter_mut() {
-                walk_schema_references(s, modify);
+                walk_schema_references(s, modify)?;
             }
         }
         SchemaKind::Not { not } => {
-            walk_box_ref_schema_references(not, modify);
+            walk_box_ref_schema_references(not, modify)?;
         }
         SchemaKind::Any(any_schema) => {
-            walk_any_schema_references(any_schema, modify);
+            walk_any_schema_references(any_schema, modify)?;
         }
     }
+    Ok(())
 }
 
-fn walk_type_references<F>(typ: &mut Type, modify: &F)
+fn walk_type_references<F, E>(typ: &mut Type, modify: &F) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     match typ {
         Type::Object(obj) => {
             for prop in obj.properties.values_mut() {
-                walk_boxed_schema_references(prop, modify);
+                walk_boxed_schema_references(prop, modify)?;
             }
-            if let Some(additional_properties) = &mut obj.additional_properties {
-                if let AdditionalProperties::Schema(s) = additional_properties {
-                    walk_box_ref_schema_references(s, modify);
-                }
+            if let Some(AdditionalProperties::Schema(s)) = &mut obj.additional_properties {
+                walk_box_ref_schema_references(s, modify)?;
             }
         }
         Type::Array(arr) => {
             if let Some(items) = &mut arr.items {
-                walk_boxed_schema_references(items, modify);
+                walk_boxed_schema_references(items, modify)?;
             }
         }
         Type::String(_) | Type::Number(_) | Type::Integer(_) | Type::Boolean {} => {}
     }
+    Ok(())
 }
 
-fn walk_any_schema_references<F>(any_schema: &mut AnySchema, modify: &F)
+fn walk_any_schema_references<F, E>(any_schema: &mut AnySchema, modify: &F) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     for prop in any_schema.properties.values_mut() {
-        walk_boxed_schema_references(prop, modify);
+        walk_boxed_schema_references(prop, modify)?;
     }
-    if let Some(additional_properties) = &mut any_schema.additional_properties {
-        if let AdditionalProperties::Schema(s) = additional_properties {
-            walk_box_ref_schema_references(s, modify);
-        }
+    if let Some(AdditionalProperties::Schema(s)) = &mut any_schema.additional_properties {
+        walk_box_ref_schema_references(s, modify)?;
     }
     if let Some(items) = &mut any_schema.items {
-        walk_boxed_schema_references(items, modify);
+        walk_boxed_schema_references(items, modify)?;
     }
     for s in any_schema.one_of.iter_mut() {
-        walk_schema_references(s, modify);
+        walk_schema_references(s, modify)?;
     }
     for s in any_schema.all_of.iter_mut() {
-        walk_schema_references(s, modify);
+        walk_schema_references(s, modify)?;
     }
     for s in any_schema.any_of.iter_mut() {
-        walk_schema_references(s, modify);
+        walk_schema_references(s, modify)?;
     }
     if let Some(not) = &mut any_schema.not {
-        walk_box_ref_schema_references(not, modify);
+        walk_box_ref_schema_references(not, modify)?;
     }
+    Ok(())
 }
 
-fn walk_media_type_references<F>(media_type: &mut MediaType, modify: &F)
+fn walk_media_type_references<F, E>(media_type: &mut MediaType, modify: &F) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     if let Some(schema) = &mut media_type.schema {
-        walk_schema_references(schema, modify);
+        walk_schema_references(schema, modify)?;
     }
     for example in media_type.examples.values_mut() {
-        walk_example_references(example, modify);
+        walk_example_references(example, modify)?;
     }
+    Ok(())
 }
 
-fn walk_example_references<F>(example: &mut ReferenceOr<Example>, modify: &F)
+fn walk_example_references<F, E>(example: &mut ReferenceOr<Example>, modify: &F) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     if let ReferenceOr::Reference { reference } = example {
-        *reference = modify(reference);
+        *reference = modify(reference)?;
     }
+    Ok(())
 }
 
-fn walk_parameter_references<F>(parameter: &mut ReferenceOr<Parameter>, modify: &F)
+fn walk_parameter_references<F, E>(
+    parameter: &mut ReferenceOr<Parameter>,
+    modify: &F,
+) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     match parameter {
         ReferenceOr::Reference { reference } => {
-            *reference = modify(reference);
+            *reference = modify(reference)?;
         }
         ReferenceOr::Item(param) => {
             // Parameter is an enum with parameter_data
             let param_data = get_parameter_data_mut(param);
-            walk_parameter_schema_or_content_references(&mut param_data.format, modify);
+            walk_parameter_schema_or_content_references(&mut param_data.format, modify)?;
             for example in param_data.examples.values_mut() {
-                walk_example_references(example, modify);
+                walk_example_references(example, modify)?;
             }
         }
     }
+    Ok(())
 }
 
 fn get_parameter_data_mut(param: &mut Parameter) -> &mut ParameterData {
@@ -250,163 +270,192 @@ fn get_parameter_data_mut(param: &mut Parameter) -> &mut ParameterData {
     }
 }
 
-fn walk_parameter_schema_or_content_references<F>(format: &mut ParameterSchemaOrContent, modify: &F)
+fn walk_parameter_schema_or_content_references<F, E>(
+    format: &mut ParameterSchemaOrContent,
+    modify: &F,
+) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     match format {
         ParameterSchemaOrContent::Schema(schema) => {
-            walk_schema_references(schema, modify);
+            walk_schema_references(schema, modify)?;
         }
         ParameterSchemaOrContent::Content(content) => {
             for media_type in content.values_mut() {
-                walk_media_type_references(media_type, modify);
+                walk_media_type_references(media_type, modify)?;
             }
         }
     }
+    Ok(())
 }
 
-fn walk_request_body_references<F>(request_body: &mut ReferenceOr<RequestBody>, modify: &F)
+fn walk_request_body_references<F, E>(
+    request_body: &mut ReferenceOr<RequestBody>,
+    modify: &F,
+) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     match request_body {
         ReferenceOr::Reference { reference } => {
-            *reference = modify(reference);
+            *reference = modify(reference)?;
         }
         ReferenceOr::Item(body) => {
             for media_type in body.content.values_mut() {
-                walk_media_type_references(media_type, modify);
+                walk_media_type_references(media_type, modify)?;
             }
         }
     }
+    Ok(())
 }
 
-fn walk_header_references<F>(header: &mut ReferenceOr<Header>, modify: &F)
+fn walk_header_references<F, E>(header: &mut ReferenceOr<Header>, modify: &F) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     match header {
         ReferenceOr::Reference { reference } => {
-            *reference = modify(reference);
+            *reference = modify(reference)?;
         }
         ReferenceOr::Item(header_item) => {
-            walk_parameter_schema_or_content_references(&mut header_item.format, modify);
+            walk_parameter_schema_or_content_references(&mut header_item.format, modify)?;
             for example in header_item.examples.values_mut() {
-                walk_example_references(example, modify);
+                walk_example_references(example, modify)?;
             }
         }
     }
+    Ok(())
 }
 
-fn walk_link_references<F>(link: &mut ReferenceOr<Link>, modify: &F)
+fn walk_link_references<F, E>(link: &mut ReferenceOr<Link>, modify: &F) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     if let ReferenceOr::Reference { reference } = link {
-        *reference = modify(reference);
+        *reference = modify(reference)?;
     }
+    Ok(())
 }
 
-fn walk_response_references<F>(response: &mut ReferenceOr<Response>, modify: &F)
+fn walk_response_references<F, E>(response: &mut ReferenceOr<Response>, modify: &F) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     match response {
         ReferenceOr::Reference { reference } => {
-            *reference = modify(reference);
+            *reference = modify(reference)?;
         }
         ReferenceOr::Item(resp) => {
             for header in resp.headers.values_mut() {
-                walk_header_references(header, modify);
+                walk_header_references(header, modify)?;
             }
             for media_type in resp.content.values_mut() {
-                walk_media_type_references(media_type, modify);
+                walk_media_type_references(media_type, modify)?;
             }
             for link in resp.links.values_mut() {
-                walk_link_references(link, modify);
+                walk_link_references(link, modify)?;
             }
         }
     }
+    Ok(())
 }
 
-fn walk_callback_references<F>(callback: &mut ReferenceOr<Callback>, modify: &F)
+fn walk_callback_references<F, E>(callback: &mut ReferenceOr<Callback>, modify: &F) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     match callback {
         ReferenceOr::Reference { reference } => {
-            *reference = modify(reference);
+            *reference = modify(reference)?;
         }
         ReferenceOr::Item(callback_item) => {
             // Callback is IndexMap<String, PathItem>, not ReferenceOr<PathItem>
             for path_item in callback_item.values_mut() {
-                walk_path_item_inner_references(path_item, modify);
+                walk_path_item_inner_references(path_item, modify)?;
             }
         }
     }
+    Ok(())
 }
 
 /// Walk references in a PathItem directly (not wrapped in ReferenceOr)
-fn walk_path_item_inner_references<F>(item: &mut PathItem, modify: &F)
+fn walk_path_item_inner_references<F, E>(item: &mut PathItem, modify: &F) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     if let Some(op) = &mut item.get {
-        walk_operation_references(op, modify);
+        walk_operation_references(op, modify)?;
     }
     if let Some(op) = &mut item.put {
-        walk_operation_references(op, modify);
+        walk_operation_references(op, modify)?;
     }
     if let Some(op) = &mut item.post {
-        walk_operation_references(op, modify);
+        walk_operation_references(op, modify)?;
     }
     if let Some(op) = &mut item.delete {
-        walk_operation_references(op, modify);
+        walk_operation_references(op, modify)?;
     }
     if let Some(op) = &mut item.options {
-        walk_operation_references(op, modify);
+        walk_operation_references(op, modify)?;
     }
     if let Some(op) = &mut item.head {
-        walk_operation_references(op, modify);
+        walk_operation_references(op, modify)?;
     }
     if let Some(op) = &mut item.patch {
-        walk_operation_references(op, modify);
+        walk_operation_references(op, modify)?;
     }
     if let Some(op) = &mut item.trace {
-        walk_operation_references(op, modify);
+        walk_operation_references(op, modify)?;
     }
     for param in item.parameters.iter_mut() {
-        walk_parameter_references(param, modify);
+        walk_parameter_references(param, modify)?;
     }
+    Ok(())
 }
 
-fn walk_component_references<F>(components: &mut Components, modify: &F)
+fn walk_component_references<F, E>(components: &mut Components, modify: &F) -> Result<(), E>
 where
-    F: Fn(&str) -> String,
+    F: Fn(&str) -> Result<String, E>,
 {
     for schema in components.schemas.values_mut() {
-        walk_schema_references(schema, modify);
+        walk_schema_references(schema, modify)?;
     }
     for response in components.responses.values_mut() {
-        walk_response_references(response, modify);
+        walk_response_references(response, modify)?;
     }
     for parameter in components.parameters.values_mut() {
-        walk_parameter_references(parameter, modify);
+        walk_parameter_references(parameter, modify)?;
     }
     for example in components.examples.values_mut() {
-        walk_example_references(example, modify);
+        walk_example_references(example, modify)?;
     }
     for request_body in components.request_bodies.values_mut() {
-        walk_request_body_references(request_body, modify);
+        walk_request_body_references(request_body, modify)?;
     }
     for header in components.headers.values_mut() {
-        walk_header_references(header, modify);
+        walk_header_references(header, modify)?;
     }
     for link in components.links.values_mut() {
-        walk_link_references(link, modify);
+        walk_link_references(link, modify)?;
     }
     for callback in components.callbacks.values_mut() {
-        walk_callback_references(callback, modify);
+        walk_callback_references(callback, modify)?;
     }
+    Ok(())
+}
+
+/// Whether the field holds literal JSON rather than part of the OpenAPI document, so that a
+/// `$ref` inside of it is not a reference: examples and `x-` extensions. The names of schema
+/// properties are not keywords, so a property named `example` is still checked.
+pub fn holds_literal_value(location: &JsonPointer, key: &str, value: &JsonValue) -> bool {
+    let segments = location.segments();
+    if segments.last().is_some_and(|parent| parent == "properties") {
+        return false;
+    }
+    let in_example_object = segments.len() >= 2 && segments[segments.len() - 2] == "examples";
+    key == "example"
+        || key.starts_with("x-")
+        || (key == "examples" && value.is_array())
+        || (key == "value" && in_example_object)
 }
@@ -1,27 +1,66 @@
 //! Tag merging logic
 
-use crate::data::MergeInput;
-use openapiv3::Tag;
-
-/// Merge tags from all inputs
-pub fn merge_tags(inputs: &MergeInput) -> Option<Vec<Tag>> {
-    let mut result = Vec::new();
-    let mut seen_tags = std::collections::HashSet::new();
-
-    for input in inputs {
-        let exclude_tags: Vec<String> = input
-            .operation_selection
-            .as_ref()
-            .and_then(|os| os.exclude_tags.as_ref())
-            .cloned()
-            .unwrap_or_default();
+use crate::data::{
+    MergeInput, MergeOptions, OperationSelection, SingleMergeInput, TagModification,
+};
+use crate::diagnostics::{Diagnostic, UNUSED_TAG};
+use crate::error::ErrorLocation;
+use crate::merge::extensions::ExtensionMerger;
+use crate::merge::json_pointer::JsonPointer;
+use crate::merge::operation_selection::{operations_mut, run_operation_selection};
+use openapiv3::{OpenAPI, ReferenceOr, Tag};
+use serde_json::{json, Value as JsonValue};
+use std::collections::HashSet;
 
+/// Merge tags from all inputs. The extensions of tags with the same name are merged together.
+///
+/// A tag definition is kept when an operation in the output uses it, no matter which input the
+/// operation came from. Unused definitions are kept only if the operation selection of their
+/// input would have selected them, and are always dropped when `drop_unused_tags` is set.
+pub fn merge_tags(
+    inputs: &MergeInput,
+    options: &MergeOptions,
+    extension_merger: &mut ExtensionMerger,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<Vec<Tag>> {
+    let mut result: Vec<Tag> = Vec::new();
+
+    let used_tags: HashSet<String> = inputs
+        .iter()
+        .flat_map(|input| selected_operation_tags(input, None))
+        .collect();
+
+    for (input_index, input) in inputs.iter().enumerate() {
         // tags is a Vec<Tag>, iterate directly
-        for tag in &input.oas.tags {
-            if !exclude_tags.contains(&tag.name) {
-                if !seen_tags.contains(&tag.name) {
-                    seen_tags.insert(tag.name.clone());
-                    result.push(tag.clone());
+        for (tag_index, original_tag) in input.oas.tags.iter().enumerate() {
+            let mut tag = original_tag.clone();
+            tag.name = modified_tag_name(&tag.name, input.tag_modification.as_ref());
+
+            let keep = used_tags.contains(&tag.name)
+                || (!options.drop_unused_tags
+                    && tag_is_selected(&original_tag.name, input.operation_selection.as_ref()));
+            if !keep {
+                continue;
+            }
+
+            match result.iter().position(|existing| existing.name == tag.name) {
+                Some(position) => extension_merger.merge(
+                    &mut result[position].extensions,
+                    &tag.extensions,
+                    &tag_pointer(position),
+                    input_index,
+                ),
+                None => {
+                    if !used_tags.contains(&tag.name) {
+                        diagnostics.push(Diagnostic::info(
+                            UNUSED_TAG,
+                            format!("The tag '{}' is defined but no operation uses it", tag.name),
+                            ErrorLocation::for_input(input_index, input)
+                                .with_input_pointer(tag_pointer(tag_index))
+                                .with_output_pointer(tag_pointer(result.len())),
+                        ));
+                    }
+                    result.push(tag)
                 }
             }
         }
@@ -33,3 +72,138 @@ pub fn merge_tags(inputs: &MergeInput) -> Option<Vec<Tag>> {
         Some(result)
     }
 }
+
+/// The pointer to a tag, e.g. `#/tags/0`
+fn tag_pointer(index: usize) -> JsonPointer {
+    JsonPointer::from_segments(["tags".to_string(), index.to_string()])
+}
+
+/// Whether the operation selection would keep operations with this tag
+fn tag_is_selected(name: &str, operation_selection: Option<&OperationSelection>) -> bool {
+    let selection = match operation_selection {
+        Some(selection) => selection,
+        None => return true,
+    };
+
+    let included = match &selection.include_tags {
+        Some(include_tags) if !include_tags.is_empty() => include_tags.iter().any(|t| t == name),
+        _ => true,
+    };
+    let excluded = selection
+        .exclude_tags
+        .as_ref()
+        .is_some_and(|exclude_tags| exclude_tags.iter().any(|t| t == name));
+
+    included && !excluded
+}
+
+/// The name of the tag group for an input: its configured name, or otherwise its info.title
+pub fn tag_group_name(input: &SingleMergeInput) -> String {
+    input
+        .name
+        .clone()
+        .unwrap_or_else(|| input.oas.info.title.clone())
+}
+
+/// The name of a tag after the tag modification of its input has been applied
+pub fn modified_tag_name(name: &str, tag_modification: Option<&TagModification>) -> String {
+    let tag_modification = match tag_modification {
+        Some(tm) => tm,
+        None => return name.to_string(),
+    };
+
+    if let Some(renamed) = tag_modification.rename.get(name) {
+        return renamed.clone();
+    }
+
+    format!(
+        "{}{}{}",
+        tag_modification.prefix.as_deref().unwrap_or(""),
+        name,
+        tag_modification.suffix.as_deref().unwrap_or("")
+    )
+}
+
+/// Rename the tags of every operation and tag definition, and add the configured tags to every
+/// operation
+pub fn apply_tag_modification(oas: &mut OpenAPI, tag_modification: Option<&TagModification>) {
+    let tag_modification = match tag_modification {
+        Some(tm) => tm,
+        None => return,
+    };
+
+    for path_item in oas.paths.paths.values_mut() {
+        if let ReferenceOr::Item(item) = path_item {
+            for operation in operations_mut(item) {
+                let mut tags: Vec<String> = Vec::new();
+                let modified_tags = operation
+                    .tags
+                    .iter()
+                    .map(|tag| modified_tag_name(tag, Some(tag_modification)))
+                    .chain(tag_modification.add_tags.iter().cloned());
+                for tag in modified_tags {
+                    if !tags.contains(&tag) {
+                        tags.push(tag);
+                    }
+                }
+                operation.tags = tags;
+            }
+        }
+    }
+
+    for tag in oas.tags.iter_mut() {
+        tag.name = modified_tag_name(&tag.name, Some(tag_modification));
+    }
+}
+
+/// Give every operation that has no tags the provided tag
+pub fn tag_untagged_operations(oas: &mut OpenAPI, tag: &str) {
+    for path_item in oas.paths.paths.values_mut() {
+        if let ReferenceOr::Item(item) = path_item {
+            for operation in operations_mut(item) {
+                if operation.tags.is_empty() {
+                    operation.tags.push(tag.to_string());
+                }
+            }
+        }
+    }
+}
+
+/// The tags used by the operations of this input that survive operation selection, in the
+/// order that they are first used
+fn selected_operation_tags(input: &SingleMergeInput, untagged_tag: Option<&str>) -> Vec<String> {
+    let mut oas = run_operation_selection(input.oas.clone(), input.operation_selection.as_ref());
+    apply_tag_modification(&mut oas, input.tag_modification.as_ref());
+    if let Some(untagged_tag) = untagged_tag {
+        tag_untagged_operations(&mut oas, untagged_tag);
+    }
+
+    let mut result: Vec<String> = Vec::new();
+    for (_, _, operation) in oas.operations() {
+        for tag in &operation.tags {
+            if !result.contains(tag) {
+                result.push(tag.clone());
+            }
+        }
+    }
+    result
+}
+
+/// Generate the value of the `x-tagGroups` extension: one group per input that contributed
+/// at least one operation
+pub fn merge_tag_groups(inputs: &MergeInput) -> JsonValue {
+    let groups: Vec<JsonValue> = inputs
+        .iter()
+        .filter_map(|input| {
+            let name = tag_group_name(input);
+            let tags = selected_operation_tags(input, Some(&name));
+            if tags.is_empty() {
+                None
+            } else {
+                Some(json!({ "name": name, "tags": tags }))
+            }
+        })
+        .collect();
+
+    JsonValue::Array(groups)
+}
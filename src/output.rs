@@ -0,0 +1,84 @@
+//! Writing the merged output
+
+use anyhow::{Context, Result};
+use openapiv3::OpenAPI;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The output path that writes to standard output instead of a file
+pub const STDOUT: &str = "-";
+
+/// How the merged output is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OutputFormat {
+    /// Indented JSON.
+    Json,
+    Yaml,
+    /// JSON on a single line, without any whitespace.
+    JsonCompact,
+}
+
+impl OutputFormat {
+    /// The format for an output path: YAML for the .yml and .yaml extensions, otherwise JSON
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml") | Some("yml") => OutputFormat::Yaml,
+            _ => OutputFormat::Json,
+        }
+    }
+
+    /// Serialize the output in this format
+    pub fn serialize<T: Serialize>(self, output: &T) -> Result<String> {
+        Ok(match self {
+            OutputFormat::Json => serde_json::to_string_pretty(output)?,
+            OutputFormat::Yaml => serde_yaml::to_string(output)?,
+            OutputFormat::JsonCompact => serde_json::to_string(output)?,
+        })
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    /// Parse the names used on the command line: `json`, `yaml` or `json-compact`
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "json-compact" => Ok(OutputFormat::JsonCompact),
+            _ => Err(format!(
+                "unknown output format '{}', expected json, yaml or json-compact",
+                name
+            )),
+        }
+    }
+}
+
+/// Write the output to a file, or to standard output if the path is `-`. Without a format, the
+/// format is chosen from the extension of the path.
+pub fn write_output(path: &Path, format: Option<OutputFormat>, output: &OpenAPI) -> Result<()> {
+    let format = format.unwrap_or_else(|| OutputFormat::for_path(path));
+    let content = format.serialize(output)?;
+
+    if is_stdout(path) {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(content.as_bytes())?;
+        if !content.ends_with('\n') {
+            stdout.write_all(b"\n")?;
+        }
+        stdout.flush()?;
+    } else {
+        std::fs::write(path, content)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Whether the output path means standard output
+pub fn is_stdout(path: &Path) -> bool {
+    path.as_os_str() == STDOUT
+}
@@ -0,0 +1,225 @@
+//! Parsing JSON and YAML sources with errors that point at the problem
+
+use crate::merge::json_pointer::JsonPointer;
+use crate::source_map::{locate_positions, SourcePosition};
+use crate::validation::validate_openapi_document;
+use openapiv3::OpenAPI;
+use serde::de::DeserializeOwned;
+use serde_json::Value as JsonValue;
+use std::fmt;
+use std::path::Path;
+
+/// The syntax of a source file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceFormat {
+    Json,
+    Yaml,
+}
+
+impl SourceFormat {
+    /// Decide the format from the extension of the file name, or from the contents when the
+    /// extension does not say. JSON documents start with `{` or `[`; anything else is YAML.
+    pub fn detect(name: &str, contents: &str) -> Self {
+        let extension = Path::new(name.split(['?', '#']).next().unwrap_or(name))
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("json") => SourceFormat::Json,
+            Some("yaml") | Some("yml") => SourceFormat::Yaml,
+            _ => {
+                let contents = contents.trim_start_matches('\u{feff}').trim_start();
+                if contents.starts_with('{') || contents.starts_with('[') {
+                    SourceFormat::Json
+                } else {
+                    SourceFormat::Yaml
+                }
+            }
+        }
+    }
+}
+
+/// A problem with a source file, located as precisely as possible
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceError {
+    /// The file or URL that was being loaded.
+    pub file: String,
+    /// Where in the file the problem is.
+    pub position: Option<SourcePosition>,
+    /// The location of the value that could not be read, if the syntax was valid.
+    pub pointer: Option<String>,
+    pub message: String,
+    /// The offending line of the source, with a marker under the column.
+    pub snippet: Option<String>,
+}
+
+impl SourceError {
+    fn new(
+        file: &str,
+        contents: &str,
+        position: Option<SourcePosition>,
+        pointer: Option<String>,
+        message: String,
+    ) -> Self {
+        Self {
+            file: file.to_string(),
+            position,
+            pointer,
+            message,
+            snippet: position.and_then(|position| snippet(contents, position)),
+        }
+    }
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(position) = self.position {
+            write!(f, ":{}:{}", position.line, position.column)?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(pointer) = &self.pointer {
+            write!(f, "\n  at {}", pointer)?;
+        }
+        if let Some(snippet) = &self.snippet {
+            write!(f, "\n{}", snippet)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SourceError {}
+
+/// A parsed source file that keeps its text, so that later errors can be located in it
+#[derive(Debug, Clone)]
+pub struct SourceDocument {
+    /// The file or URL the source was loaded from.
+    pub file: String,
+    pub contents: String,
+    pub format: SourceFormat,
+    pub document: JsonValue,
+}
+
+impl SourceDocument {
+    /// Parse the contents of a file as JSON or YAML, depending on its extension or contents
+    pub fn parse(file: &str, contents: String) -> Result<Self, SourceError> {
+        let format = SourceFormat::detect(file, &contents);
+        let document = match format {
+            SourceFormat::Json => serde_json::from_str::<JsonValue>(&contents).map_err(|e| {
+                let position = (e.line() > 0).then(|| SourcePosition {
+                    line: e.line(),
+                    column: e.column(),
+                });
+                SourceError::new(
+                    file,
+                    &contents,
+                    position,
+                    None,
+                    format!("Invalid JSON: {}", strip_location(&e.to_string())),
+                )
+            })?,
+            SourceFormat::Yaml => serde_yaml::from_str::<JsonValue>(&contents).map_err(|e| {
+                let position = e.location().map(|location| SourcePosition {
+                    line: location.line(),
+                    column: location.column(),
+                });
+                SourceError::new(
+                    file,
+                    &contents,
+                    position,
+                    None,
+                    format!("Invalid YAML: {}", strip_location(&e.to_string())),
+                )
+            })?,
+        };
+
+        Ok(Self {
+            file: file.to_string(),
+            contents,
+            format,
+            document,
+        })
+    }
+
+    /// Deserialize the document, locating the value that does not have the expected shape
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, SourceError> {
+        serde_path_to_error::deserialize(self.document.clone()).map_err(|e| {
+            let pointer = path_to_pointer(e.path());
+            self.error_at(&pointer, e.inner().to_string())
+        })
+    }
+
+    /// Deserialize the document as an OpenAPI file. When serde can only say that a value did not
+    /// match any of its possible shapes, the OpenAPI JSON Schema is used to explain why.
+    pub fn to_openapi(&self) -> Result<OpenAPI, SourceError> {
+        self.deserialize::<OpenAPI>().map_err(|error| {
+            if !error.message.contains("did not match any variant") {
+                return error;
+            }
+            let failed_at = error.pointer.clone().unwrap_or_else(|| "#".to_string());
+            let finding = validate_openapi_document(&self.document)
+                .into_iter()
+                .find(|finding| {
+                    finding.pointer == failed_at
+                        || finding.pointer.starts_with(&format!("{}/", failed_at))
+                });
+            match finding {
+                Some(finding) => match JsonPointer::parse(&finding.pointer) {
+                    Some(pointer) => self.error_at(&pointer, finding.message),
+                    None => error,
+                },
+                None => error,
+            }
+        })
+    }
+
+    fn error_at(&self, pointer: &JsonPointer, message: String) -> SourceError {
+        let positions = locate_positions(&self.contents);
+        let position = (0..=pointer.segments().len())
+            .rev()
+            .map(|length| JsonPointer::from_segments(&pointer.segments()[..length]))
+            .find_map(|ancestor| positions.get(&ancestor).copied());
+        SourceError::new(
+            &self.file,
+            &self.contents,
+            position,
+            Some(pointer.to_string()),
+            message,
+        )
+    }
+}
+
+fn path_to_pointer(path: &serde_path_to_error::Path) -> JsonPointer {
+    let mut segments = Vec::new();
+    for segment in path {
+        match segment {
+            serde_path_to_error::Segment::Seq { index } => segments.push(index.to_string()),
+            serde_path_to_error::Segment::Map { key } => segments.push(key.clone()),
+            serde_path_to_error::Segment::Enum { .. } => {}
+            serde_path_to_error::Segment::Unknown => break,
+        }
+    }
+    JsonPointer::from_segments(segments)
+}
+
+/// serde appends " at line X column Y" to its messages, which the error already reports
+fn strip_location(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(index) => message[..index].to_string(),
+        None => message.to_string(),
+    }
+}
+
+fn snippet(contents: &str, position: SourcePosition) -> Option<String> {
+    let line = contents.lines().nth(position.line.checked_sub(1)?)?;
+    let gutter = position.line.to_string();
+    let marker_indent: String = line
+        .chars()
+        .take(position.column.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    Some(format!(
+        "{gutter} | {line}\n{blank} | {marker_indent}^",
+        blank = " ".repeat(gutter.len())
+    ))
+}
@@ -0,0 +1,153 @@
+//! A local HTTP server that previews the merged output
+
+use anyhow::{anyhow, Result};
+use openapiv3::OpenAPI;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// The page served at `/`. It renders `/openapi.json` with Redoc and reloads itself whenever
+/// `/status` reports a new version of the output.
+const DOCS_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+  <head>
+    <meta charset="utf-8">
+    <title>openapi-merge preview</title>
+    <style>
+      body { margin: 0; }
+      #merge-error { display: none; margin: 0; padding: 1em; background: #fdd; color: #900; white-space: pre-wrap; font-family: monospace; }
+    </style>
+  </head>
+  <body>
+    <pre id="merge-error"></pre>
+    <redoc spec-url="/openapi.json"></redoc>
+    <script src="https://cdn.redoc.ly/redoc/latest/bundles/redoc.standalone.js"></script>
+    <script>
+      let shownVersion = null;
+      async function poll() {
+        try {
+          const status = await (await fetch("/status", { cache: "no-store" })).json();
+          if (shownVersion === null) {
+            shownVersion = status.version;
+          } else if (status.version !== shownVersion) {
+            location.reload();
+            return;
+          }
+          const banner = document.getElementById("merge-error");
+          banner.textContent = status.error || "";
+          banner.style.display = status.error ? "block" : "none";
+        } catch (e) {
+          // The server is restarting; try again on the next poll
+        }
+        setTimeout(poll, 1000);
+      }
+      poll();
+    </script>
+  </body>
+</html>
+"#;
+
+#[derive(Debug, Default)]
+struct PreviewState {
+    json: Option<String>,
+    yaml: Option<String>,
+    /// Incremented every time the output changes, so that the docs page knows to reload.
+    version: u64,
+    /// Why the latest merge failed, if it did. The last good output is still served.
+    error: Option<String>,
+}
+
+/// The merged output being served, shared between the server and whatever merges the inputs
+#[derive(Debug, Clone, Default)]
+pub struct Preview {
+    state: Arc<Mutex<PreviewState>>,
+}
+
+impl Preview {
+    /// Serve a new merged output
+    pub fn update(&self, output: &OpenAPI) -> Result<()> {
+        let json = serde_json::to_string_pretty(output)?;
+        let yaml = serde_yaml::to_string(output)?;
+        let mut state = self.lock();
+        state.json = Some(json);
+        state.yaml = Some(yaml);
+        state.version += 1;
+        state.error = None;
+        Ok(())
+    }
+
+    /// Record that the latest merge failed, keeping the last good output
+    pub fn fail(&self, error: impl Into<String>) {
+        self.lock().error = Some(error.into());
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PreviewState> {
+        // A panic while holding the lock cannot leave the state half written
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn respond(&self, request: &Request) -> Response<std::io::Cursor<Vec<u8>>> {
+        if *request.method() != Method::Get && *request.method() != Method::Head {
+            return text_response(405, "Method not allowed");
+        }
+
+        let path = request.url().split('?').next().unwrap_or("");
+        let state = self.lock();
+        let document = match path {
+            "/" | "/index.html" => return content_response(DOCS_PAGE, "text/html; charset=utf-8"),
+            "/status" => {
+                let status = json!({ "version": state.version, "error": state.error });
+                return content_response(&status.to_string(), "application/json");
+            }
+            "/openapi.json" => state.json.as_deref().map(|json| (json, "application/json")),
+            "/openapi.yaml" => state.yaml.as_deref().map(|yaml| (yaml, "application/yaml")),
+            _ => return text_response(404, "Not found"),
+        };
+
+        match document {
+            Some((content, content_type)) => content_response(content, content_type),
+            None => text_response(
+                503,
+                state
+                    .error
+                    .as_deref()
+                    .unwrap_or("The inputs have not been merged yet"),
+            ),
+        }
+    }
+}
+
+/// Serve the preview on `address`, e.g. `127.0.0.1:8080`, from a background thread:
+///
+/// - `/` renders the output with Redoc and reloads when it changes
+/// - `/openapi.json` and `/openapi.yaml` serve the output
+/// - `/status` reports the version of the output and the error from the latest merge
+pub fn serve(address: &str, preview: Preview) -> Result<JoinHandle<()>> {
+    let server =
+        Server::http(address).map_err(|e| anyhow!("Failed to listen on {}: {}", address, e))?;
+    Ok(thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = preview.respond(&request);
+            // The client may have gone away; there is nobody to report that to
+            let _ = request.respond(response);
+        }
+    }))
+}
+
+fn content_response(content: &str, content_type: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_data(content.as_bytes().to_vec())
+        .with_header(header("Content-Type", content_type))
+        .with_header(header("Cache-Control", "no-store"))
+}
+
+fn text_response(status: u16, message: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    content_response(message, "text/plain; charset=utf-8").with_status_code(status)
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes())
+        .expect("header names and values are ASCII")
+}
@@ -0,0 +1,329 @@
+//! Source maps from locations in the merged output to locations in the inputs
+
+use crate::data::MergeInput;
+use crate::merge::json_pointer::JsonPointer;
+use crate::merge::origins::{original_location, Origins};
+use indexmap::IndexMap;
+use openapiv3::OpenAPI;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+
+/// A line and column in a source file, both starting at 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SourcePosition {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// The position of every value in a source file, keyed by its JSON pointer. Values in an object
+/// are positioned at their key.
+pub type SourcePositions = HashMap<JsonPointer, SourcePosition>;
+
+/// Where a single location in the merged output came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceMapEntry {
+    /// The index of the input in the configuration.
+    pub input: usize,
+    /// The file or URL that the input was loaded from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// The location in the input, before any renames or path modifications.
+    #[serde(rename = "originalPointer")]
+    pub original_pointer: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+}
+
+/// A map from every traced location in the merged output to the input it came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceMap {
+    pub version: u32,
+    pub mappings: IndexMap<String, SourceMapEntry>,
+}
+
+/// Build the source map of a merged output. `positions` holds the source positions of each
+/// input, in input order; inputs without positions are mapped without lines and columns.
+/// Locations that were composed from several inputs, such as `info` and `tags`, are not mapped,
+/// and neither are locations that the merge added, such as `x-merge-source` and added tags, whose
+/// original pointer does not exist in the input.
+pub fn build_source_map(
+    output: &OpenAPI,
+    inputs: &MergeInput,
+    origins: &Origins,
+    positions: &[SourcePositions],
+) -> SourceMap {
+    let mut locations = Vec::new();
+    if let Ok(document) = serde_json::to_value(output) {
+        collect_locations(&document, &JsonPointer::root(), &mut locations);
+    }
+
+    let documents: Vec<Option<JsonValue>> = inputs
+        .iter()
+        .map(|input| serde_json::to_value(&input.oas).ok())
+        .collect();
+
+    let mappings = locations
+        .into_iter()
+        .filter_map(|location| {
+            let origin = original_location(origins, &location)?;
+            let position = positions
+                .get(origin.input_index)
+                .and_then(|positions| positions.get(&origin.original_pointer));
+            let exists_in_input = documents
+                .get(origin.input_index)
+                .and_then(Option::as_ref)
+                .and_then(|document| origin.original_pointer.resolve(document))
+                .is_some();
+            if position.is_none() && !exists_in_input {
+                return None;
+            }
+            let entry = SourceMapEntry {
+                input: origin.input_index,
+                file: inputs
+                    .get(origin.input_index)
+                    .and_then(|input| input.source.clone()),
+                original_pointer: origin.original_pointer.to_string(),
+                line: position.map(|position| position.line),
+                column: position.map(|position| position.column),
+            };
+            Some((location.to_string(), entry))
+        })
+        .collect();
+
+    SourceMap {
+        version: 1,
+        mappings,
+    }
+}
+
+fn collect_locations(value: &JsonValue, location: &JsonPointer, locations: &mut Vec<JsonPointer>) {
+    locations.push(location.clone());
+    match value {
+        JsonValue::Object(fields) => {
+            for (key, child) in fields {
+                collect_locations(child, &location.child(key.as_str()), locations);
+            }
+        }
+        JsonValue::Array(items) => {
+            for (index, child) in items.iter().enumerate() {
+                collect_locations(child, &location.child(index.to_string()), locations);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Find the position of every value in a YAML or JSON source. If the source cannot be parsed,
+/// the positions found before the problem are returned.
+pub fn locate_positions(contents: &str) -> SourcePositions {
+    let mut locator = PositionLocator::default();
+    let _ = Parser::new_from_str(contents).load(&mut locator, false);
+    locator.positions
+}
+
+enum Frame {
+    Mapping {
+        key: Option<(String, Marker)>,
+        first_key: bool,
+    },
+    Sequence {
+        next_index: usize,
+    },
+}
+
+#[derive(Default)]
+struct PositionLocator {
+    positions: SourcePositions,
+    frames: Vec<Frame>,
+    segments: Vec<String>,
+}
+
+impl PositionLocator {
+    /// Record a value that starts at `mark`, returning the segment it is stored under
+    fn enter_value(&mut self, mark: Marker) -> Option<Option<String>> {
+        let (segment, mark) = match self.frames.last_mut() {
+            None => (None, mark),
+            Some(Frame::Sequence { next_index }) => {
+                *next_index += 1;
+                (Some((*next_index - 1).to_string()), mark)
+            }
+            Some(Frame::Mapping { key, .. }) => match key.take() {
+                Some((key, key_mark)) => (Some(key), key_mark),
+                None => return None,
+            },
+        };
+
+        let mut segments = self.segments.clone();
+        segments.extend(segment.clone());
+        self.positions.insert(
+            JsonPointer::from_segments(segments),
+            SourcePosition {
+                line: mark.line(),
+                column: mark.col() + 1,
+            },
+        );
+        Some(segment)
+    }
+
+    fn enter_container(&mut self, mark: Marker, frame: Frame) {
+        // Containers used as mapping keys cannot appear in an OpenAPI document, so their
+        // contents are only given approximate positions
+        let segment = self
+            .enter_value(mark)
+            .unwrap_or_else(|| Some(String::new()));
+        if let Some(segment) = &segment {
+            self.segments.push(segment.clone());
+        }
+        self.frames.push(frame);
+    }
+
+    /// Block mappings only start once the parser has seen the colon after their first key, so
+    /// position a mapping that is not the value of a key at its first key instead
+    fn move_to_first_key(&mut self, mark: Marker) {
+        let pointer = JsonPointer::from_segments(self.segments.clone());
+        if let Some(position) = self.positions.get_mut(&pointer) {
+            let first_key = SourcePosition {
+                line: mark.line(),
+                column: mark.col() + 1,
+            };
+            if (first_key.line, first_key.column) < (position.line, position.column) {
+                *position = first_key;
+            }
+        }
+    }
+
+    fn leave_container(&mut self) {
+        self.frames.pop();
+        if !self.frames.is_empty() {
+            self.segments.pop();
+        }
+    }
+}
+
+impl MarkedEventReceiver for PositionLocator {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(value, ..) => {
+                if let Some(Frame::Mapping {
+                    key: key @ None,
+                    first_key,
+                }) = self.frames.last_mut()
+                {
+                    *key = Some((value, mark));
+                    if std::mem::take(first_key) {
+                        self.move_to_first_key(mark);
+                    }
+                } else {
+                    self.enter_value(mark);
+                }
+            }
+            Event::Alias(_) => {
+                self.enter_value(mark);
+            }
+            Event::MappingStart(..) => self.enter_container(
+                mark,
+                Frame::Mapping {
+                    key: None,
+                    first_key: true,
+                },
+            ),
+            Event::SequenceStart(..) => {
+                self.enter_container(mark, Frame::Sequence { next_index: 0 })
+            }
+            Event::MappingEnd | Event::SequenceEnd => self.leave_container(),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{MergeOptions, PathModification, SingleMergeInput, TagModification};
+    use crate::merge::merge;
+    use serde_json::json;
+
+    fn input(document: JsonValue) -> SingleMergeInput {
+        SingleMergeInput {
+            oas: serde_json::from_value(document).unwrap(),
+            name: None,
+            source: None,
+            path_modification: None,
+            operation_selection: None,
+            tag_modification: None,
+            description: None,
+            dispute: None,
+            dispute_prefix: None,
+            modified: None,
+        }
+    }
+
+    #[test]
+    fn locations_added_by_the_merge_are_not_mapped() {
+        let users = json!({
+            "openapi": "3.0.3",
+            "info": { "title": "Users", "version": "1" },
+            "paths": {
+                "/users": {
+                    "get": { "responses": { "200": { "description": "ok" } } }
+                }
+            }
+        });
+        let billing = json!({
+            "openapi": "3.0.3",
+            "info": { "title": "Billing", "version": "1" },
+            "paths": {
+                "/users/{id}": {
+                    "get": { "responses": { "200": { "description": "ok" } } },
+                    "post": { "responses": { "201": { "description": "created" } } }
+                }
+            }
+        });
+        let positions = vec![
+            locate_positions(&serde_json::to_string_pretty(&users).unwrap()),
+            locate_positions(&serde_json::to_string_pretty(&billing).unwrap()),
+        ];
+        let mut billing = input(billing);
+        billing.path_modification = Some(PathModification {
+            strip_start: None,
+            prepend: Some("/billing".to_string()),
+        });
+        billing.tag_modification = Some(TagModification {
+            rename: Default::default(),
+            prefix: None,
+            suffix: None,
+            add_tags: vec!["Billing".to_string()],
+        });
+        let inputs = vec![input(users), billing];
+        let options = MergeOptions {
+            annotate_sources: true,
+            ..Default::default()
+        };
+
+        let result = merge(&inputs, &options).unwrap();
+        let source_map = build_source_map(&result.output, &inputs, &result.origins, &positions);
+
+        let operation = &source_map.mappings["#/paths/~1billing~1users~1{id}/post"];
+        assert_eq!(operation.input, 1);
+        assert_eq!(operation.original_pointer, "#/paths/~1users~1{id}/post");
+        assert!(operation.line.is_some());
+        for added in [
+            "#/paths/~1billing~1users~1{id}/x-merge-source",
+            "#/paths/~1billing~1users~1{id}/post/x-merge-source",
+            "#/paths/~1billing~1users~1{id}/post/x-merge-source/input",
+            "#/paths/~1billing~1users~1{id}/get/tags",
+            "#/paths/~1billing~1users~1{id}/get/tags/0",
+        ] {
+            assert!(
+                !source_map.mappings.contains_key(added),
+                "{} should not be mapped",
+                added
+            );
+        }
+    }
+}
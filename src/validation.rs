@@ -0,0 +1,128 @@
+//! Validation of OpenAPI documents against the official OpenAPI JSON Schemas
+
+use jsonschema::{Draft, JSONSchema};
+use serde_json::Value as JsonValue;
+use std::sync::OnceLock;
+
+const OPENAPI_3_0_SCHEMA: &str = include_str!("../schemas/openapi-3.0.json");
+const OPENAPI_3_1_SCHEMA: &str = include_str!("../schemas/openapi-3.1.json");
+
+/// A place where a document does not match the OpenAPI JSON Schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaFinding {
+    /// The location of the problem in the document, e.g. `#/paths/~1users/get`.
+    pub pointer: String,
+    /// A description of the problem.
+    pub message: String,
+}
+
+/// Validate a document against the OpenAPI JSON Schema that matches its `openapi` version.
+/// Documents that do not declare a 3.0 or 3.1 version produce a single finding.
+pub fn validate_openapi_document(document: &JsonValue) -> Vec<SchemaFinding> {
+    let version = document
+        .get("openapi")
+        .and_then(JsonValue::as_str)
+        .unwrap_or("");
+
+    let schema = if version.starts_with("3.0") {
+        openapi_3_0_schema()
+    } else if version.starts_with("3.1") {
+        openapi_3_1_schema()
+    } else {
+        return vec![SchemaFinding {
+            pointer: "#/openapi".to_string(),
+            message: format!(
+                "'{}' is not a supported OpenAPI version; expected 3.0.x or 3.1.x",
+                version
+            ),
+        }];
+    };
+
+    let schema = match schema {
+        Ok(schema) => schema,
+        Err(message) => {
+            return vec![SchemaFinding {
+                pointer: "#".to_string(),
+                message: format!(
+                    "The OpenAPI {} JSON Schema could not be compiled, so the document was not \
+                     validated: {}",
+                    &version[..3],
+                    message
+                ),
+            }];
+        }
+    };
+
+    let mut findings: Vec<SchemaFinding> = Vec::new();
+    if let Err(errors) = schema.validate(document) {
+        for error in errors {
+            let finding = SchemaFinding {
+                pointer: format!("#{}", error.instance_path),
+                message: error.to_string(),
+            };
+            // The same problem can be reached through more than one path in the schema
+            if !findings.contains(&finding) {
+                findings.push(finding);
+            }
+        }
+    }
+    findings
+}
+
+fn openapi_3_0_schema() -> Result<&'static JSONSchema, &'static str> {
+    static SCHEMA: OnceLock<Result<JSONSchema, String>> = OnceLock::new();
+    SCHEMA
+        .get_or_init(|| compile_schema(OPENAPI_3_0_SCHEMA, Draft::Draft4))
+        .as_ref()
+        .map_err(String::as_str)
+}
+
+fn openapi_3_1_schema() -> Result<&'static JSONSchema, &'static str> {
+    static SCHEMA: OnceLock<Result<JSONSchema, String>> = OnceLock::new();
+    SCHEMA
+        .get_or_init(|| compile_schema(OPENAPI_3_1_SCHEMA, Draft::Draft202012))
+        .as_ref()
+        .map_err(String::as_str)
+}
+
+/// Compile a bundled OpenAPI schema. Compiling can fail, for example when the schema refers to
+/// a meta-schema that cannot be fetched, so the problem is returned rather than panicking.
+fn compile_schema(source: &str, draft: Draft) -> Result<JSONSchema, String> {
+    let schema: JsonValue = serde_json::from_str(source).map_err(|e| e.to_string())?;
+    JSONSchema::options()
+        .with_draft(draft)
+        .compile(&schema)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn validates_openapi_3_1_documents() {
+        let mut document = json!({
+            "openapi": "3.1.0",
+            "info": { "title": "Pets", "version": "1" },
+            "paths": {
+                "/pets": {
+                    "get": { "responses": { "200": { "description": "ok" } } }
+                }
+            },
+            "components": {
+                "schemas": {
+                    "Pet": { "type": ["object", "null"], "properties": { "name": true } }
+                }
+            }
+        });
+        assert_eq!(validate_openapi_document(&document), []);
+
+        document["paths"]["/pets"]["get"]["responses"]["200"] = json!({ "content": {} });
+        let findings = validate_openapi_document(&document);
+        assert!(!findings.is_empty());
+        assert!(findings
+            .iter()
+            .all(|finding| finding.pointer.starts_with("#/paths/~1pets/get")));
+    }
+}
@@ -0,0 +1,112 @@
+//! Watching local files for changes
+
+use crate::data::{Configuration, ConfigurationInput};
+use crate::external_references::referenced_files;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the watched files are checked for changes
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long the watched files must stay unchanged before a change is reported, so that an
+/// editor saving several files at once causes a single merge
+pub const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Every local file that a merge with this configuration reads: the input files, the files
+/// that their references lead to when they are resolved, and the Markdown files for
+/// descriptions. Inputs loaded from URLs are not included.
+pub fn local_files(base_path: &Path, config: &Configuration) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for input in &config.inputs {
+        if let ConfigurationInput::FromFile(file_input) = input {
+            let path = base_path.join(&file_input.input_file);
+            if input.resolve_external_references() {
+                files.extend(referenced_files(&path));
+            }
+            files.push(path);
+        }
+        if let Some(file) = input.description().and_then(|d| d.file.as_ref()) {
+            files.push(base_path.join(file));
+        }
+    }
+    if let Some(description) = &config.description {
+        for file in [&description.preamble_file, &description.postamble_file]
+            .into_iter()
+            .flatten()
+        {
+            files.push(base_path.join(file));
+        }
+    }
+    files
+}
+
+/// Polls a set of files for changes to their modification times. Files that do not exist are
+/// watched too, so that creating them counts as a change.
+#[derive(Debug, Clone)]
+pub struct FileWatcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl FileWatcher {
+    /// Start watching the files as they are now
+    pub fn new(files: impl IntoIterator<Item = PathBuf>) -> Self {
+        let mut paths: Vec<PathBuf> = files.into_iter().collect();
+        paths.sort();
+        paths.dedup();
+        Self {
+            files: paths
+                .into_iter()
+                .map(|path| {
+                    let modified = modified_time(&path);
+                    (path, modified)
+                })
+                .collect(),
+        }
+    }
+
+    /// The files being watched
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|(path, _)| path.as_path())
+    }
+
+    /// The files that changed since they were last checked, updating their modification times
+    pub fn changed_files(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        for (path, modified) in &mut self.files {
+            let current = modified_time(path);
+            if current != *modified {
+                *modified = current;
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+
+    /// Block until at least one file changes and then stays unchanged for `DEBOUNCE`,
+    /// returning every file that changed in that time
+    pub fn wait_for_changes(&mut self) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = Vec::new();
+        let mut last_change = Instant::now();
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let newly_changed = self.changed_files();
+            if !newly_changed.is_empty() {
+                for path in newly_changed {
+                    if !changed.contains(&path) {
+                        changed.push(path);
+                    }
+                }
+                last_change = Instant::now();
+            } else if !changed.is_empty() && last_change.elapsed() >= DEBOUNCE {
+                return changed;
+            }
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    path.metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}